@@ -0,0 +1,356 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::behaviour::{Behaviour, Context};
+use crate::bus::event::{Event, Ldr, MotorCommand, MotorDirection};
+
+pub const NAME: &str = "line-follow";
+
+/// Sensor level read over the line
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Polarity {
+    /// 1 over the line, as the kit's tracking module reads on a dark line
+    High,
+    /// 0 over the line, e.g. a light line on a dark floor
+    Low,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LineFollowConfig {
+    pub polarity: Polarity,
+    /// Speed while the line is under the middle sensor
+    pub speed: u8,
+    /// Speed of the spin back onto the line when it drifts to one side
+    pub turn_speed: u8,
+    pub search_speed: u8,
+    /// How long the line may be out of sight before searching for it
+    pub lost_ms: u64,
+    /// First sweep of the search, each later sweep turns back for longer
+    pub sweep_ms: u64,
+    /// Give up and stop after searching this long
+    pub search_secs: f64,
+}
+
+impl Default for LineFollowConfig {
+    fn default() -> Self {
+        LineFollowConfig {
+            polarity: Polarity::High,
+            speed: 60,
+            turn_speed: 70,
+            search_speed: 60,
+            lost_ms: 300,
+            sweep_ms: 400,
+            search_secs: 10.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn other(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+        }
+    }
+
+    fn direction(self) -> MotorDirection {
+        match self {
+            Side::Left => MotorDirection::Left,
+            Side::Right => MotorDirection::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// On the line, steering back towards it if it drifts to a side
+    Following(Option<Side>),
+    /// Sweeping side to side for the line, each sweep wider than the last
+    Searching {
+        side: Side,
+        sweep: u32,
+        until: Instant,
+    },
+    /// Stopped until the line passes under a sensor again
+    Lost,
+}
+
+impl State {
+    fn name(self) -> &'static str {
+        match self {
+            State::Following(None) => "following/straight",
+            State::Following(Some(Side::Left)) => "following/left",
+            State::Following(Some(Side::Right)) => "following/right",
+            State::Searching { .. } => "searching",
+            State::Lost => "lost",
+        }
+    }
+}
+
+/// Follows a line with the three LDR sensors under the front of the robot
+pub struct LineFollow {
+    config: LineFollowConfig,
+    state: Option<State>,
+    // Side the line was last seen on, where the search starts
+    last_side: Side,
+    lost_since: Option<Instant>,
+    search_started: Option<Instant>,
+    last_command: Option<MotorCommand>,
+}
+
+impl LineFollow {
+    pub fn new(config: LineFollowConfig) -> Self {
+        LineFollow {
+            config,
+            state: None,
+            last_side: Side::Left,
+            lost_since: None,
+            search_started: None,
+            last_command: None,
+        }
+    }
+
+    fn enter(&mut self, next: State, reason: &'static str, ctx: &mut Context) {
+        let from = self.state.map(State::name);
+
+        // Search sweeps re-enter Searching, but the other states only report
+        // actual changes
+        if from == Some(next.name()) && !matches!(next, State::Searching { .. }) {
+            return;
+        }
+
+        self.state = Some(next);
+
+        let c = &self.config;
+        let (direction, speed) = match next {
+            State::Following(None) => (MotorDirection::Forward, c.speed),
+            State::Following(Some(side)) => (side.direction(), c.turn_speed),
+            State::Searching { side, .. } => (side.direction(), c.search_speed),
+            State::Lost => (MotorDirection::Stop, 0),
+        };
+        self.drive(MotorCommand { direction, speed }, ctx);
+
+        println!("[{NAME}] {} ({reason})", next.name());
+        ctx.transition(NAME, from, next.name(), reason);
+    }
+
+    // Only sends the motors a command when it differs from the last one
+    fn drive(&mut self, cmd: MotorCommand, ctx: &mut Context) {
+        if self.last_command.as_ref() != Some(&cmd) {
+            ctx.drive(cmd.direction.clone(), cmd.speed);
+            self.last_command = Some(cmd);
+        }
+    }
+
+    fn on_ldr(&mut self, ldr: &Ldr, ctx: &mut Context) {
+        let on = |level: u8| match self.config.polarity {
+            Polarity::High => level == 1,
+            Polarity::Low => level == 0,
+        };
+        let (left, middle, right) = (on(ldr.l_val), on(ldr.m_val), on(ldr.r_val));
+
+        if !(left || middle || right) {
+            self.lost_since.get_or_insert(ctx.now);
+            return;
+        }
+
+        self.lost_since = None;
+        self.search_started = None;
+
+        // Turn towards whichever outer sensor sees the line. Both or
+        // neither, with the middle on it, means carry straight on.
+        let steer = match (left, right) {
+            (true, false) => Some(Side::Left),
+            (false, true) => Some(Side::Right),
+            _ => None,
+        };
+        if let Some(side) = steer {
+            self.last_side = side;
+        }
+
+        let reason = match self.state {
+            Some(State::Following(_)) => "line moved",
+            _ => "line found",
+        };
+        self.enter(State::Following(steer), reason, ctx);
+    }
+}
+
+impl Behaviour for LineFollow {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn on_enter(&mut self, ctx: &mut Context) {
+        *self = LineFollow::new(self.config.clone());
+        self.lost_since = Some(ctx.now);
+
+        if let Some(ldr) = ctx.state.ldr.clone() {
+            self.on_ldr(&ldr, ctx);
+        }
+    }
+
+    fn on_event(&mut self, event: &Event, ctx: &mut Context) {
+        if let Event::Ldr(ldr) = event {
+            self.on_ldr(ldr, ctx);
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &mut Context) {
+        let Some(lost_since) = self.lost_since else {
+            return;
+        };
+        let LineFollowConfig {
+            lost_ms,
+            sweep_ms,
+            search_secs,
+            ..
+        } = self.config;
+        let sweep_time = |sweep: u32| Duration::from_millis(sweep_ms * sweep as u64);
+
+        match self.state {
+            None | Some(State::Following(_))
+                if ctx.now.duration_since(lost_since) >= Duration::from_millis(lost_ms) =>
+            {
+                self.search_started = Some(ctx.now);
+
+                let next = State::Searching {
+                    side: self.last_side,
+                    sweep: 1,
+                    until: ctx.now + sweep_time(1),
+                };
+                self.enter(next, "line lost", ctx);
+            }
+            Some(State::Searching { side, sweep, until }) if ctx.now >= until => {
+                let searching = self
+                    .search_started
+                    .map_or(Duration::ZERO, |started| ctx.now.duration_since(started));
+
+                if searching >= Duration::from_secs_f64(search_secs) {
+                    self.enter(State::Lost, "search timed out", ctx);
+                } else {
+                    let next = State::Searching {
+                        side: side.other(),
+                        sweep: sweep + 1,
+                        until: ctx.now + sweep_time(sweep + 1),
+                    };
+                    self.enter(next, "sweeping back", ctx);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::testing::{motor, ms, run, transitions};
+    use crate::bus::state::RobotState;
+
+    fn ldr(l_val: u8, m_val: u8, r_val: u8) -> Event {
+        Event::Ldr(Ldr {
+            l_val,
+            m_val,
+            r_val,
+        })
+    }
+
+    #[test]
+    fn steers_towards_the_line() {
+        let state = RobotState::default();
+        let config = LineFollowConfig::default();
+        let t0 = Instant::now();
+        let mut follow = LineFollow::new(config.clone());
+        run(&state, t0, |ctx| follow.on_enter(ctx));
+
+        let events = run(&state, t0, |ctx| follow.on_event(&ldr(0, 1, 0), ctx));
+        assert_eq!(transitions(&events), ["following/straight"]);
+        assert_eq!(
+            motor(&events),
+            Some(&MotorCommand {
+                direction: MotorDirection::Forward,
+                speed: config.speed,
+            })
+        );
+
+        let events = run(&state, t0, |ctx| follow.on_event(&ldr(1, 1, 0), ctx));
+        assert_eq!(transitions(&events), ["following/left"]);
+        assert_eq!(
+            motor(&events),
+            Some(&MotorCommand {
+                direction: MotorDirection::Left,
+                speed: config.turn_speed,
+            })
+        );
+
+        // The same reading again changes nothing
+        let events = run(&state, t0, |ctx| follow.on_event(&ldr(1, 1, 0), ctx));
+        assert!(events.is_empty());
+
+        let events = run(&state, t0, |ctx| follow.on_event(&ldr(0, 0, 1), ctx));
+        assert_eq!(transitions(&events), ["following/right"]);
+    }
+
+    #[test]
+    fn low_polarity_follows_a_light_line() {
+        let state = RobotState::default();
+        let t0 = Instant::now();
+        let mut follow = LineFollow::new(LineFollowConfig {
+            polarity: Polarity::Low,
+            ..LineFollowConfig::default()
+        });
+        run(&state, t0, |ctx| follow.on_enter(ctx));
+
+        let events = run(&state, t0, |ctx| follow.on_event(&ldr(1, 1, 0), ctx));
+        assert_eq!(transitions(&events), ["following/right"]);
+    }
+
+    #[test]
+    fn searches_where_the_line_was_last_seen_then_gives_up() {
+        let state = RobotState::default();
+        let config = LineFollowConfig {
+            search_secs: 1.0,
+            ..LineFollowConfig::default()
+        };
+        let t0 = Instant::now();
+        let mut follow = LineFollow::new(config.clone());
+        run(&state, t0, |ctx| follow.on_enter(ctx));
+        run(&state, t0, |ctx| follow.on_event(&ldr(0, 0, 1), ctx));
+
+        // Still within `lost_ms` of losing it
+        run(&state, t0, |ctx| follow.on_event(&ldr(0, 0, 0), ctx));
+        let events = run(&state, t0 + ms(config.lost_ms - 1), |ctx| {
+            follow.on_tick(ctx)
+        });
+        assert!(events.is_empty());
+
+        let mut t = t0 + ms(config.lost_ms);
+        let events = run(&state, t, |ctx| follow.on_tick(ctx));
+        assert_eq!(transitions(&events), ["searching"]);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Right);
+
+        // Each sweep turns back the other way for longer
+        t += ms(config.sweep_ms);
+        let events = run(&state, t, |ctx| follow.on_tick(ctx));
+        assert_eq!(transitions(&events), ["searching"]);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Left);
+
+        t += ms(config.sweep_ms * 2);
+        let events = run(&state, t, |ctx| follow.on_tick(ctx));
+        assert_eq!(transitions(&events), ["lost"]);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Stop);
+
+        let events = run(&state, t, |ctx| follow.on_event(&ldr(0, 1, 0), ctx));
+        assert_eq!(transitions(&events), ["following/straight"]);
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::behaviour::{
+    Behaviour, Context,
+    tree::{self, Node, Status},
+};
+
+pub const NAME: &str = "mission";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MissionConfig {
+    /// Behaviour tree to run, JSON or TOML. Read each time the mission is
+    /// selected, so it can be edited while the robot is running.
+    pub path: PathBuf,
+}
+
+impl Default for MissionConfig {
+    fn default() -> Self {
+        MissionConfig {
+            path: PathBuf::from("mission.toml"),
+        }
+    }
+}
+
+/// Runs a behaviour tree loaded from `[mission] path`, once, then holds
+/// still until selected again
+pub struct Mission {
+    config: MissionConfig,
+    root: Option<Node>,
+}
+
+impl Mission {
+    pub fn new(config: MissionConfig) -> Self {
+        Mission { config, root: None }
+    }
+}
+
+impl Behaviour for Mission {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn on_enter(&mut self, ctx: &mut Context) {
+        match tree::load(&self.config.path) {
+            Ok(root) => {
+                println!("[{NAME}] running {}", self.config.path.display());
+                self.root = Some(root);
+                ctx.transition(NAME, None, "running", "loaded");
+            }
+            Err(e) => {
+                eprintln!("[{NAME}] {e:#}");
+                self.root = None;
+                ctx.transition(NAME, None, "failed", "load error");
+            }
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &mut Context) {
+        let Some(root) = self.root.as_mut() else {
+            return;
+        };
+
+        let (to, reason) = match root.tick(ctx) {
+            Status::Running => return,
+            Status::Success => ("succeeded", "tree succeeded"),
+            Status::Failure => ("failed", "tree failed"),
+        };
+
+        println!("[{NAME}] {to}");
+        self.root = None;
+        ctx.stop();
+        ctx.transition(NAME, Some("running"), to, reason);
+    }
+
+    fn on_exit(&mut self, ctx: &mut Context) {
+        if let Some(mut root) = self.root.take() {
+            root.halt(ctx);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+pub mod line_follow;
+pub mod mission;
+pub mod script;
+pub mod sentry;
+pub mod tree;
+pub mod wall_follow;
+pub mod wander;
+
+use std::time::Instant;
+
+use crate::{
+    bus::{
+        event::{BehaviourTransition, Event, MotorCommand, MotorDirection, WheelCommand},
+        state::RobotState,
+    },
+    config::Config,
+};
+
+use self::{
+    line_follow::LineFollow, mission::Mission, script::Script, sentry::Sentry,
+    wall_follow::WallFollow, wander::Wander,
+};
+
+/// Behaviour `mode.automatic` selects when no name is given
+pub const DEFAULT: &str = wander::NAME;
+
+type Factory = fn(&Config) -> Box<dyn Behaviour>;
+
+/// Every behaviour `Mode::Automatic` can run, in the order the dashboard
+/// lists them
+pub const REGISTRY: &[(&str, Factory)] = &[
+    (wander::NAME, |config| {
+        Box::new(Wander::new(config.wander.clone(), config.servo.axis))
+    }),
+    (line_follow::NAME, |config| {
+        Box::new(LineFollow::new(config.line_follow.clone()))
+    }),
+    (wall_follow::NAME, |config| {
+        Box::new(WallFollow::new(
+            config.wall_follow.clone(),
+            config.servo.clone(),
+        ))
+    }),
+    (sentry::NAME, |config| {
+        Box::new(Sentry::new(
+            config.sentry.clone(),
+            config.servo.clone(),
+            config.vision.motion.enabled,
+        ))
+    }),
+    (mission::NAME, |config| {
+        Box::new(Mission::new(config.mission.clone()))
+    }),
+    (script::NAME, |config| {
+        Box::new(Script::new(config.script.clone()))
+    }),
+];
+
+/// Registered name matching `name`, as the `'static` str modes carry
+pub fn lookup(name: &str) -> Option<&'static str> {
+    REGISTRY.iter().map(|(n, _)| *n).find(|n| *n == name)
+}
+
+pub fn create(name: &str, config: &Config) -> Option<Box<dyn Behaviour>> {
+    REGISTRY
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, factory)| factory(config))
+}
+
+/// What a behaviour sees on each call, and where it leaves the commands it
+/// wants published. Behaviours never touch the bus directly, so they can be
+/// driven with synthetic events and their output inspected.
+pub struct Context<'a> {
+    pub state: &'a RobotState,
+    pub now: Instant,
+    commands: Vec<Event>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(state: &'a RobotState, now: Instant) -> Self {
+        Context {
+            state,
+            now,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn publish(&mut self, event: Event) {
+        self.commands.push(event);
+    }
+
+    pub fn drive(&mut self, direction: MotorDirection, speed: u8) {
+        self.publish(Event::MotorCommand(MotorCommand { direction, speed }));
+    }
+
+    /// Steers by driving each side at its own speed, -100..=100
+    pub fn wheels(&mut self, left: i8, right: i8) {
+        self.publish(Event::WheelCommand(WheelCommand { left, right }));
+    }
+
+    pub fn stop(&mut self) {
+        self.drive(MotorDirection::Stop, 0);
+    }
+
+    /// Tells the UI which state `behaviour` has moved into and why
+    pub fn transition(
+        &mut self,
+        behaviour: &'static str,
+        from: Option<&'static str>,
+        to: &'static str,
+        reason: &'static str,
+    ) {
+        self.publish(Event::BehaviourTransition(BehaviourTransition {
+            behaviour,
+            from,
+            to,
+            reason,
+        }));
+    }
+
+    /// Commands published so far, in order
+    pub fn into_commands(self) -> Vec<Event> {
+        self.commands
+    }
+}
+
+/// A policy for driving the robot in automatic mode. The behaviour node
+/// calls `on_enter` when the mode selects it, `on_event` for every bus event
+/// and `on_tick` every 200ms while it is active, and `on_exit` when the mode
+/// changes away.
+pub trait Behaviour: Send {
+    fn name(&self) -> &'static str;
+
+    fn on_enter(&mut self, _ctx: &mut Context) {}
+
+    fn on_event(&mut self, _event: &Event, _ctx: &mut Context) {}
+
+    fn on_tick(&mut self, _ctx: &mut Context) {}
+
+    fn on_exit(&mut self, _ctx: &mut Context) {}
+}
+
+/// Drives behaviours through `Context` without a bus, for their tests
+#[cfg(test)]
+pub mod testing {
+    use std::time::{Duration, Instant};
+
+    use super::Context;
+    use crate::bus::{
+        event::{Event, MotorCommand, Ultrasound, WheelCommand},
+        state::RobotState,
+    };
+
+    pub fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// An ultrasound reading of `distance` cm
+    pub fn reading(distance: f64) -> Event {
+        Event::Ultrasound(Ultrasound { distance })
+    }
+
+    /// Runs one hook at `now` and returns what it published
+    pub fn run(state: &RobotState, now: Instant, hook: impl FnOnce(&mut Context)) -> Vec<Event> {
+        let mut ctx = Context::new(state, now);
+        hook(&mut ctx);
+        ctx.into_commands()
+    }
+
+    /// States moved into, in order
+    pub fn transitions(events: &[Event]) -> Vec<&'static str> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::BehaviourTransition(t) => Some(t.to),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Reason given for the last transition
+    pub fn reason(events: &[Event]) -> Option<&'static str> {
+        events.iter().rev().find_map(|event| match event {
+            Event::BehaviourTransition(t) => Some(t.reason),
+            _ => None,
+        })
+    }
+
+    pub fn motor(events: &[Event]) -> Option<&MotorCommand> {
+        events.iter().rev().find_map(|event| match event {
+            Event::MotorCommand(cmd) => Some(cmd),
+            _ => None,
+        })
+    }
+
+    pub fn wheels(events: &[Event]) -> Option<WheelCommand> {
+        events.iter().rev().find_map(|event| match event {
+            Event::WheelCommand(wheels) => Some(*wheels),
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result, anyhow};
+use rhai::{AST, Dynamic, Engine, EvalAltResult, Map};
+use serde::Deserialize;
+
+use crate::{
+    behaviour::{Behaviour, Context},
+    bus::{
+        event::{Event, LedCommand, MotorCommand, MotorDirection, ServoCommand},
+        state::RobotState,
+    },
+};
+
+pub const NAME: &str = "script";
+
+// Longest a script sleeps or waits without checking whether to stop
+const WAIT_SLICE: Duration = Duration::from_millis(20);
+
+// Shortest a script sleeps or waits, so `sleep(0)` in a loop can't be used
+// to dodge `max_busy_ms`
+const MIN_WAIT: Duration = Duration::from_millis(10);
+
+// Events queued for a script that isn't calling `next_event`
+const EVENT_QUEUE: usize = 32;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScriptConfig {
+    /// Rhai script to run, replaced by `PUT /api/script`
+    pub path: PathBuf,
+    /// Stop the script after this many seconds, 0 for no limit
+    pub max_secs: f64,
+    /// Longest the script may compute between `sleep` or `next_event`
+    /// calls before it is stopped as a runaway loop
+    pub max_busy_ms: u64,
+}
+
+impl Default for ScriptConfig {
+    fn default() -> Self {
+        ScriptConfig {
+            path: PathBuf::from("script.rhai"),
+            max_secs: 600.0,
+            max_busy_ms: 500,
+        }
+    }
+}
+
+/// Checks `source` compiles, without running it
+pub fn check(source: &str) -> Result<()> {
+    sandbox().compile(source)?;
+
+    Ok(())
+}
+
+// Engine with no access to files or modules and bounded memory use
+fn sandbox() -> Engine {
+    let mut engine = Engine::new();
+
+    engine.set_max_modules(0);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(10_000);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(1_000);
+
+    engine
+}
+
+// Shared between the behaviour and the thread running the script
+struct Shared {
+    stop: AtomicBool,
+    state: RwLock<RobotState>,
+    // Last time the script slept or waited for an event
+    last_wait: Mutex<Instant>,
+    commands: Mutex<Commands>,
+}
+
+// Latest command of each kind the script has sent since they were last
+// published. A script looping on `drive` only ever has one waiting, rather
+// than a backlog flooding the bus.
+#[derive(Default)]
+struct Commands {
+    motor: Option<Event>,
+    servo: Option<Event>,
+    leds: Option<Event>,
+}
+
+impl Commands {
+    fn push(&mut self, event: Event) {
+        match event {
+            Event::MotorCommand(_) => self.motor = Some(event),
+            Event::ServoCommand(_) => self.servo = Some(event),
+            Event::LedCommand(_) => self.leds = Some(event),
+            _ => {}
+        }
+    }
+
+    fn take(&mut self) -> impl Iterator<Item = Event> {
+        [self.motor.take(), self.servo.take(), self.leds.take()]
+            .into_iter()
+            .flatten()
+    }
+}
+
+impl Shared {
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    // Sleeps in slices so a stop request is noticed straight away
+    fn pause(&self, duration: Duration) {
+        let until = Instant::now() + duration.max(MIN_WAIT);
+
+        while !self.stopped() {
+            let now = Instant::now();
+            if now >= until {
+                break;
+            }
+
+            std::thread::sleep(WAIT_SLICE.min(until - now));
+        }
+
+        *self.last_wait.lock().unwrap() = Instant::now();
+    }
+}
+
+/// Runs a user script written in Rhai, read from `[script] path` when
+/// selected. The script runs on its own thread and drives the robot
+/// through a small API; it is stopped as soon as the mode changes.
+pub struct Script {
+    config: ScriptConfig,
+    run: Option<Run>,
+}
+
+// A script that is currently running
+struct Run {
+    shared: Arc<Shared>,
+    finished: Receiver<Result<(), String>>,
+    events: SyncSender<Dynamic>,
+}
+
+impl Script {
+    pub fn new(config: ScriptConfig) -> Self {
+        Script { config, run: None }
+    }
+
+    fn start(&self, state: &RobotState) -> Result<Run> {
+        let path = &self.config.path;
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let ast = sandbox()
+            .compile(&source)
+            .map_err(|e| anyhow!("{}: {e}", path.display()))?;
+
+        let shared = Arc::new(Shared {
+            stop: AtomicBool::new(false),
+            state: RwLock::new(state.clone()),
+            last_wait: Mutex::new(Instant::now()),
+            commands: Mutex::default(),
+        });
+        let (finished_tx, finished) = mpsc::channel();
+        let (events, events_rx) = mpsc::sync_channel(EVENT_QUEUE);
+
+        let engine = self.engine(shared.clone(), events_rx);
+
+        std::thread::Builder::new()
+            .name("script".to_string())
+            .spawn(move || {
+                let result = run_script(&engine, &ast);
+                let _ = finished_tx.send(result);
+            })?;
+
+        Ok(Run {
+            shared,
+            finished,
+            events,
+        })
+    }
+
+    fn engine(&self, shared: Arc<Shared>, events: Receiver<Dynamic>) -> Engine {
+        let mut engine = sandbox();
+        let started = Instant::now();
+        let max_run =
+            (self.config.max_secs > 0.0).then(|| Duration::from_secs_f64(self.config.max_secs));
+        let max_busy = Duration::from_millis(self.config.max_busy_ms);
+
+        let s = shared.clone();
+        engine.on_progress(move |ops| {
+            if s.stopped() {
+                return Some("stopped".into());
+            }
+
+            // Reading the clock on every operation would slow scripts down
+            if ops % 1024 != 0 {
+                return None;
+            }
+
+            if max_run.is_some_and(|max| started.elapsed() > max) {
+                return Some("ran out of time".into());
+            }
+            if s.last_wait.lock().unwrap().elapsed() > max_busy {
+                return Some("busy too long without sleeping".into());
+            }
+
+            None
+        });
+
+        engine.on_print(|text| println!("[{NAME}] {text}"));
+
+        // Sensors and mode, from the latest robot state
+        let s = shared.clone();
+        engine.register_fn("distance", move || -> Dynamic {
+            let state = s.state.read().unwrap();
+            state
+                .ultrasound
+                .as_ref()
+                .map_or(Dynamic::UNIT, |u| u.distance.into())
+        });
+        let s = shared.clone();
+        engine.register_fn("ldr", move || -> Dynamic {
+            let state = s.state.read().unwrap();
+            state.ldr.as_ref().map_or(Dynamic::UNIT, |ldr| {
+                let mut map = Map::new();
+                map.insert("left".into(), (ldr.l_val as i64).into());
+                map.insert("middle".into(), (ldr.m_val as i64).into());
+                map.insert("right".into(), (ldr.r_val as i64).into());
+                map.into()
+            })
+        });
+        let s = shared.clone();
+        engine.register_fn("servo_angle", move || -> Dynamic {
+            let state = s.state.read().unwrap();
+            state
+                .servo
+                .as_ref()
+                .map_or(Dynamic::UNIT, |servo| (servo.angle as i64).into())
+        });
+        let s = shared.clone();
+        engine.register_fn("mode", move || s.state.read().unwrap().mode.to_string());
+
+        // Commands, dropped once the script has been told to stop
+        let send = {
+            let s = shared.clone();
+            move |event: Event| {
+                if !s.stopped() {
+                    s.commands.lock().unwrap().push(event);
+                }
+            }
+        };
+
+        let tx = send.clone();
+        engine.register_fn(
+            "drive",
+            move |direction: &str, speed: i64| -> Result<(), Box<EvalAltResult>> {
+                tx(Event::MotorCommand(MotorCommand {
+                    direction: parse_direction(direction)?,
+                    speed: in_range(speed, 0, 100, "speed")?,
+                }));
+                Ok(())
+            },
+        );
+        let tx = send.clone();
+        engine.register_fn("stop", move || {
+            tx(Event::MotorCommand(MotorCommand {
+                direction: MotorDirection::Stop,
+                speed: 0,
+            }))
+        });
+        let tx = send.clone();
+        engine.register_fn(
+            "servo",
+            move |angle: i64| -> Result<(), Box<EvalAltResult>> {
+                tx(Event::ServoCommand(ServoCommand {
+                    angle: in_range(angle, 0, 180, "angle")?,
+                }));
+                Ok(())
+            },
+        );
+        let tx = send;
+        engine.register_fn(
+            "leds",
+            move |red: i64, green: i64, blue: i64, ms: i64| -> Result<(), Box<EvalAltResult>> {
+                tx(Event::LedCommand(LedCommand {
+                    red: in_range(red, 0, 255, "red")?,
+                    green: in_range(green, 0, 255, "green")?,
+                    blue: in_range(blue, 0, 255, "blue")?,
+                    duration: Duration::from_millis(ms.max(0) as u64),
+                }));
+                Ok(())
+            },
+        );
+
+        // Waiting, the only way a script gets to run for long
+        let s = shared.clone();
+        engine.register_fn("sleep", move |ms: i64| {
+            s.pause(Duration::from_millis(ms.max(0) as u64))
+        });
+        let s = shared;
+        let events = Mutex::new(events);
+        engine.register_fn("next_event", move |ms: i64| -> Dynamic {
+            let events = events.lock().unwrap();
+            let until = Instant::now() + Duration::from_millis(ms.max(0) as u64).max(MIN_WAIT);
+            let mut event = Dynamic::UNIT;
+
+            while !s.stopped() {
+                let now = Instant::now();
+                if now >= until {
+                    break;
+                }
+
+                match events.recv_timeout(WAIT_SLICE.min(until - now)) {
+                    Ok(next) => {
+                        event = next;
+                        break;
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+
+            *s.last_wait.lock().unwrap() = Instant::now();
+            event
+        });
+
+        engine
+    }
+
+    // Publishes whatever the script has asked for since the last call
+    fn drain(&mut self, ctx: &mut Context) {
+        let Some(run) = self.run.as_ref() else {
+            return;
+        };
+
+        for event in run.shared.commands.lock().unwrap().take() {
+            ctx.publish(event);
+        }
+
+        let Ok(result) = run.finished.try_recv() else {
+            return;
+        };
+
+        self.run = None;
+        ctx.stop();
+
+        match result {
+            Ok(()) => {
+                println!("[{NAME}] finished");
+                ctx.transition(NAME, Some("running"), "finished", "script ended");
+            }
+            Err(e) => {
+                eprintln!("[{NAME}] {e}");
+                ctx.transition(NAME, Some("running"), "failed", "script error");
+            }
+        }
+    }
+}
+
+impl Behaviour for Script {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn on_enter(&mut self, ctx: &mut Context) {
+        match self.start(ctx.state) {
+            Ok(run) => {
+                println!("[{NAME}] running {}", self.config.path.display());
+                self.run = Some(run);
+                ctx.transition(NAME, None, "running", "started");
+            }
+            Err(e) => {
+                eprintln!("[{NAME}] {e:#}");
+                ctx.transition(NAME, None, "failed", "load error");
+            }
+        }
+    }
+
+    fn on_event(&mut self, event: &Event, ctx: &mut Context) {
+        if let Some(run) = self.run.as_ref()
+            && let Some(event) = script_event(event)
+        {
+            // Nobody is listening if the queue is full
+            let _ = run.events.try_send(event);
+        }
+
+        self.drain(ctx);
+    }
+
+    fn on_tick(&mut self, ctx: &mut Context) {
+        if let Some(run) = self.run.as_ref() {
+            *run.shared.state.write().unwrap() = ctx.state.clone();
+        }
+
+        self.drain(ctx);
+    }
+
+    fn on_exit(&mut self, _ctx: &mut Context) {
+        if let Some(run) = self.run.take() {
+            run.shared.stop.store(true, Ordering::Relaxed);
+            println!("[{NAME}] stopped");
+        }
+    }
+}
+
+fn run_script(engine: &Engine, ast: &AST) -> Result<(), String> {
+    engine.run_ast(ast).map_err(|e| match *e {
+        // Carries the reason given by `on_progress`
+        EvalAltResult::ErrorTerminated(reason, _) => format!("Script stopped: {reason}"),
+        e => e.to_string(),
+    })
+}
+
+// Bus events a script can receive from `next_event`, as maps with a `type`
+fn script_event(event: &Event) -> Option<Dynamic> {
+    let mut map = Map::new();
+    let mut set = |key: &str, value: Dynamic| {
+        map.insert(key.into(), value);
+    };
+
+    match event {
+        Event::Ultrasound(ultrasound) => {
+            set("type", "ultrasound".into());
+            set("distance", ultrasound.distance.into());
+        }
+        Event::Ldr(ldr) => {
+            set("type", "ldr".into());
+            set("left", (ldr.l_val as i64).into());
+            set("middle", (ldr.m_val as i64).into());
+            set("right", (ldr.r_val as i64).into());
+        }
+        Event::BlobDetected(blob) => {
+            set("type", "blob".into());
+            set("label", blob.label.clone().into());
+            set("cx", (blob.cx as i64).into());
+            set("cy", (blob.cy as i64).into());
+            set("area", blob.area.into());
+        }
+        Event::FaceDetected(face) => {
+            set("type", "face".into());
+            set("cx", (face.cx as i64).into());
+            set("cy", (face.cy as i64).into());
+            set("faces", (face.faces as i64).into());
+        }
+        Event::MotionDetected(motion) => {
+            set("type", "motion".into());
+            set("fraction", motion.fraction.into());
+            set("cx", (motion.cx as i64).into());
+            set("cy", (motion.cy as i64).into());
+        }
+        Event::QrCodeDetected(qr) => {
+            set("type", "qr".into());
+            set("text", qr.text.clone().into());
+        }
+        Event::FiducialDetected(fiducial) => {
+            set("type", "fiducial".into());
+            set("id", (fiducial.id as i64).into());
+        }
+        _ => return None,
+    }
+
+    Some(map.into())
+}
+
+fn parse_direction(direction: &str) -> Result<MotorDirection, Box<EvalAltResult>> {
+    match direction {
+        "forward" => Ok(MotorDirection::Forward),
+        "backward" => Ok(MotorDirection::Backward),
+        "left" => Ok(MotorDirection::Left),
+        "right" => Ok(MotorDirection::Right),
+        _ => Err(format!("Unknown direction {direction:?}").into()),
+    }
+}
+
+fn in_range(value: i64, min: i64, max: i64, what: &str) -> Result<u8, Box<EvalAltResult>> {
+    if (min..=max).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(format!("{what} must be {min}..={max}, got {value}").into())
+    }
+}
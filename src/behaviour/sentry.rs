@@ -0,0 +1,243 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::{
+    behaviour::{Behaviour, Context},
+    bus::event::{Event, LedCommand, Motion, RecordCommand, ServoCommand},
+    nodes::servo::{SERVO_CENTRE, ServoConfig},
+};
+
+pub const NAME: &str = "sentry";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SentryConfig {
+    /// Seconds recorded after the last motion
+    pub clip_secs: u64,
+    /// Seconds the neopixels flash for
+    pub flash_secs: u64,
+    pub flash_colour: [u8; 3],
+    /// Point the camera servo at the moving region
+    pub aim: bool,
+}
+
+impl Default for SentryConfig {
+    fn default() -> Self {
+        SentryConfig {
+            clip_secs: 30,
+            flash_secs: 3,
+            flash_colour: [255, 0, 0],
+            aim: true,
+        }
+    }
+}
+
+/// Keeps the robot still and reacts to `MotionDetected`: flashes the
+/// neopixels, records a clip and aims the camera at the movement
+pub struct Sentry {
+    config: SentryConfig,
+    servo: ServoConfig,
+    // Whether `[vision.motion]` is on, without it there's nothing to react to
+    detecting: bool,
+    // When the clip sentry started should stop, if one is running
+    clip_until: Option<Instant>,
+}
+
+impl Sentry {
+    pub fn new(config: SentryConfig, servo: ServoConfig, detecting: bool) -> Self {
+        Sentry {
+            config,
+            servo,
+            detecting,
+            clip_until: None,
+        }
+    }
+
+    fn end_clip(&mut self, ctx: &mut Context) {
+        if self.clip_until.take().is_some() {
+            ctx.publish(Event::RecordCommand(RecordCommand { recording: false }));
+        }
+    }
+
+    fn on_motion(&mut self, motion: &Motion, ctx: &mut Context) {
+        println!(
+            "[sentry] motion over {:.1}% of the frame at ({}, {})",
+            motion.fraction * 100.0,
+            motion.cx,
+            motion.cy
+        );
+
+        let [red, green, blue] = self.config.flash_colour;
+        ctx.publish(Event::LedCommand(LedCommand {
+            red,
+            green,
+            blue,
+            duration: Duration::from_secs(self.config.flash_secs),
+        }));
+
+        // Extend our own clip, but leave a recording someone else started alone
+        let clip_until = ctx.now + Duration::from_secs(self.config.clip_secs);
+        let recording = ctx.state.recording.as_ref().is_some_and(|r| r.recording);
+
+        if self.clip_until.is_some() {
+            self.clip_until = Some(clip_until);
+        } else if !recording {
+            ctx.publish(Event::RecordCommand(RecordCommand { recording: true }));
+            self.clip_until = Some(clip_until);
+        }
+
+        if self.config.aim {
+            let current = ctx.state.servo.as_ref().map_or(SERVO_CENTRE, |s| s.angle);
+            let correction = self.servo.correction(
+                motion.cx,
+                motion.cy,
+                motion.frame_width,
+                motion.frame_height,
+            );
+            let angle = self.servo.clamp(current as f64 + correction);
+
+            if angle != current {
+                ctx.publish(Event::ServoCommand(ServoCommand { angle }));
+            }
+        }
+    }
+}
+
+impl Behaviour for Sentry {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn on_enter(&mut self, ctx: &mut Context) {
+        ctx.stop();
+
+        if !self.detecting {
+            eprintln!("[{NAME}] needs motion detection, see [vision.motion] enabled");
+            ctx.transition(NAME, None, "failed", "motion detection is disabled");
+            return;
+        }
+
+        println!("[sentry] watching for motion");
+        ctx.transition(NAME, None, "watching", "started");
+    }
+
+    fn on_event(&mut self, event: &Event, ctx: &mut Context) {
+        if let Event::MotionDetected(motion) = event {
+            self.on_motion(motion, ctx);
+        }
+    }
+
+    /// Ends the clip once no motion has been seen for `clip_secs`
+    fn on_tick(&mut self, ctx: &mut Context) {
+        if self.clip_until.is_some_and(|until| ctx.now >= until) {
+            self.end_clip(ctx);
+        }
+    }
+
+    fn on_exit(&mut self, ctx: &mut Context) {
+        self.end_clip(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::testing::{motor, run, transitions};
+    use crate::bus::{
+        event::{BoundingBox, MotorDirection, Recording},
+        state::RobotState,
+    };
+
+    // Motion in the right quarter of a 640x480 frame
+    fn motion() -> Event {
+        Event::MotionDetected(Motion {
+            fraction: 0.1,
+            bbox: BoundingBox {
+                x: 440,
+                y: 200,
+                width: 80,
+                height: 80,
+            },
+            cx: 480,
+            cy: 240,
+            frame_width: 640,
+            frame_height: 480,
+        })
+    }
+
+    fn recording(events: &[Event]) -> Vec<bool> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::RecordCommand(cmd) => Some(cmd.recording),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fails_without_motion_detection() {
+        let mut sentry = Sentry::new(SentryConfig::default(), ServoConfig::default(), false);
+
+        let events = run(&RobotState::default(), Instant::now(), |ctx| {
+            sentry.on_enter(ctx)
+        });
+        assert_eq!(transitions(&events), ["failed"]);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Stop);
+    }
+
+    #[test]
+    fn flashes_records_and_aims_at_motion() {
+        let state = RobotState::default();
+        let config = SentryConfig::default();
+        let t0 = Instant::now();
+        let mut sentry = Sentry::new(config.clone(), ServoConfig::default(), true);
+
+        let events = run(&state, t0, |ctx| sentry.on_enter(ctx));
+        assert_eq!(transitions(&events), ["watching"]);
+
+        let events = run(&state, t0, |ctx| sentry.on_event(&motion(), ctx));
+        assert!(events.iter().any(|e| matches!(e, Event::LedCommand(_))));
+        assert_eq!(recording(&events), [true]);
+
+        // A quarter of the frame right of centre is a quarter of the view
+        let angle = events.iter().find_map(|event| match event {
+            Event::ServoCommand(cmd) => Some(cmd.angle),
+            _ => None,
+        });
+        assert_eq!(angle, Some(SERVO_CENTRE + 16));
+
+        // More motion keeps the same clip going for longer
+        let later = t0 + Duration::from_secs(10);
+        let events = run(&state, later, |ctx| sentry.on_event(&motion(), ctx));
+        assert!(recording(&events).is_empty());
+
+        let clip = Duration::from_secs(config.clip_secs);
+        let events = run(&state, t0 + clip, |ctx| sentry.on_tick(ctx));
+        assert!(recording(&events).is_empty());
+
+        let events = run(&state, later + clip, |ctx| sentry.on_tick(ctx));
+        assert_eq!(recording(&events), [false]);
+    }
+
+    #[test]
+    fn leaves_someone_elses_recording_alone() {
+        let state = RobotState {
+            recording: Some(Recording {
+                recording: true,
+                segment: None,
+            }),
+            ..RobotState::default()
+        };
+        let t0 = Instant::now();
+        let mut sentry = Sentry::new(SentryConfig::default(), ServoConfig::default(), true);
+        run(&state, t0, |ctx| sentry.on_enter(ctx));
+
+        let events = run(&state, t0, |ctx| sentry.on_event(&motion(), ctx));
+        assert!(recording(&events).is_empty());
+
+        let events = run(&state, t0, |ctx| sentry.on_exit(ctx));
+        assert!(recording(&events).is_empty());
+    }
+}
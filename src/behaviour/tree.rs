@@ -0,0 +1,803 @@
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context as _, Result, bail};
+use serde::Deserialize;
+
+use crate::behaviour::Context;
+use crate::bus::event::{Event, LedCommand, MotorDirection, ServoCommand};
+
+// Speed for `drive` and `turn` leaves that don't give one
+const DEFAULT_SPEED: u8 = 100;
+
+/// Result of ticking a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Success,
+    Failure,
+    Running,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Forward,
+    Backward,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn motor(self) -> MotorDirection {
+        match self {
+            Direction::Forward => MotorDirection::Forward,
+            Direction::Backward => MotorDirection::Backward,
+            Direction::Left => MotorDirection::Left,
+            Direction::Right => MotorDirection::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sensor {
+    /// Ultrasound, cm
+    Distance,
+    LdrLeft,
+    LdrMiddle,
+    LdrRight,
+    /// Servo angle, degrees
+    Servo,
+}
+
+/// A tree as written in a mission file. Every node is a table with a
+/// `type` naming its kind, e.g. `{ "type": "wait", "ms": 500 }`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeDef {
+    /// Runs children in order until one fails
+    Sequence {
+        children: Vec<NodeDef>,
+    },
+    /// Runs children in order until one succeeds
+    Selector {
+        children: Vec<NodeDef>,
+    },
+    /// Ticks every child together. Succeeds once `success` of them have
+    /// (all by default), fails once that can no longer happen.
+    Parallel {
+        children: Vec<NodeDef>,
+        success: Option<usize>,
+    },
+    /// Re-runs a failing child up to `times` more times
+    Retry {
+        times: u32,
+        child: Box<NodeDef>,
+    },
+    /// Runs a child `times` times, or forever, stopping at the first failure
+    Repeat {
+        times: Option<u32>,
+        child: Box<NodeDef>,
+    },
+    /// Fails a child that is still running after `ms`
+    Timeout {
+        ms: u64,
+        child: Box<NodeDef>,
+    },
+    /// Swaps success and failure
+    Inverter {
+        child: Box<NodeDef>,
+    },
+    /// Runs a child only while `condition` holds. Other nodes check
+    /// conditions once, as they reach them; a guard re-checks it every
+    /// tick, and halts the child and fails as soon as it stops succeeding.
+    Guard {
+        condition: Box<NodeDef>,
+        child: Box<NodeDef>,
+    },
+    /// Sets the motors, and stops them again after `ms` if given
+    Drive {
+        direction: Direction,
+        #[serde(default = "default_speed")]
+        speed: u8,
+        ms: Option<u64>,
+    },
+    /// Spins on the spot, and stops again after `ms` if given
+    Turn {
+        direction: Side,
+        #[serde(default = "default_speed")]
+        speed: u8,
+        ms: Option<u64>,
+    },
+    Stop,
+    Servo {
+        angle: u8,
+    },
+    /// Flashes the neopixels for `ms`
+    Leds {
+        colour: [u8; 3],
+        ms: u64,
+    },
+    Wait {
+        ms: u64,
+    },
+    /// Succeeds while the sensor reads below and/or above the given values,
+    /// fails otherwise or before the first reading
+    Check {
+        sensor: Sensor,
+        below: Option<f64>,
+        above: Option<f64>,
+    },
+}
+
+fn default_speed() -> u8 {
+    DEFAULT_SPEED
+}
+
+/// Reads a tree from a `.json` file, or TOML for any other extension, with
+/// the root node under a `root` key
+pub fn load(path: &Path) -> Result<Node> {
+    #[derive(Deserialize)]
+    struct File {
+        root: NodeDef,
+    }
+
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let file: File = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&text)?
+    } else {
+        toml::from_str(&text)?
+    };
+
+    Node::build(file.root).with_context(|| format!("Invalid tree in {}", path.display()))
+}
+
+/// Leaf that talks to the bus
+#[derive(Debug)]
+pub enum Action {
+    Move {
+        direction: MotorDirection,
+        speed: u8,
+        duration: Option<Duration>,
+    },
+    Stop,
+    Servo(u8),
+    Leds([u8; 3], Duration),
+    Wait(Duration),
+}
+
+/// A tree ready to tick, holding the progress of each node
+#[derive(Debug)]
+pub enum Node {
+    Sequence {
+        children: Vec<Node>,
+        current: usize,
+    },
+    Selector {
+        children: Vec<Node>,
+        current: usize,
+    },
+    Parallel {
+        children: Vec<Node>,
+        done: Vec<Option<Status>>,
+        success: usize,
+    },
+    Retry {
+        child: Box<Node>,
+        times: u32,
+        failures: u32,
+    },
+    Repeat {
+        child: Box<Node>,
+        times: Option<u32>,
+        runs: u32,
+    },
+    Timeout {
+        child: Box<Node>,
+        limit: Duration,
+        started: Option<Instant>,
+    },
+    Inverter {
+        child: Box<Node>,
+    },
+    Guard {
+        condition: Box<Node>,
+        child: Box<Node>,
+    },
+    Action {
+        action: Action,
+        started: Option<Instant>,
+    },
+    Check {
+        sensor: Sensor,
+        below: Option<f64>,
+        above: Option<f64>,
+    },
+}
+
+impl Node {
+    pub fn build(def: NodeDef) -> Result<Node> {
+        let build_all = |children: Vec<NodeDef>| -> Result<Vec<Node>> {
+            if children.is_empty() {
+                bail!("Composite node with no children");
+            }
+
+            children.into_iter().map(Node::build).collect()
+        };
+        let build_one =
+            |child: Box<NodeDef>| -> Result<Box<Node>> { Ok(Box::new(Node::build(*child)?)) };
+        let action = |action| Node::Action {
+            action,
+            started: None,
+        };
+
+        let node = match def {
+            NodeDef::Sequence { children } => Node::Sequence {
+                children: build_all(children)?,
+                current: 0,
+            },
+            NodeDef::Selector { children } => Node::Selector {
+                children: build_all(children)?,
+                current: 0,
+            },
+            NodeDef::Parallel { children, success } => {
+                let children = build_all(children)?;
+                let success = success.unwrap_or(children.len());
+
+                if success == 0 || success > children.len() {
+                    bail!(
+                        "Parallel success must be 1..={}, got {success}",
+                        children.len()
+                    );
+                }
+
+                Node::Parallel {
+                    done: vec![None; children.len()],
+                    children,
+                    success,
+                }
+            }
+            NodeDef::Retry { times, child } => Node::Retry {
+                child: build_one(child)?,
+                times,
+                failures: 0,
+            },
+            NodeDef::Repeat { times, child } => Node::Repeat {
+                child: build_one(child)?,
+                times,
+                runs: 0,
+            },
+            NodeDef::Timeout { ms, child } => Node::Timeout {
+                child: build_one(child)?,
+                limit: Duration::from_millis(ms),
+                started: None,
+            },
+            NodeDef::Inverter { child } => Node::Inverter {
+                child: build_one(child)?,
+            },
+            NodeDef::Guard { condition, child } => Node::Guard {
+                condition: build_one(condition)?,
+                child: build_one(child)?,
+            },
+            NodeDef::Drive {
+                direction,
+                speed,
+                ms,
+            } => action(Action::Move {
+                direction: direction.motor(),
+                speed,
+                duration: ms.map(Duration::from_millis),
+            }),
+            NodeDef::Turn {
+                direction,
+                speed,
+                ms,
+            } => action(Action::Move {
+                direction: match direction {
+                    Side::Left => MotorDirection::Left,
+                    Side::Right => MotorDirection::Right,
+                },
+                speed,
+                duration: ms.map(Duration::from_millis),
+            }),
+            NodeDef::Stop => action(Action::Stop),
+            NodeDef::Servo { angle } => {
+                if angle > 180 {
+                    bail!("Servo angle must be 0..=180, got {angle}");
+                }
+
+                action(Action::Servo(angle))
+            }
+            NodeDef::Leds { colour, ms } => action(Action::Leds(colour, Duration::from_millis(ms))),
+            NodeDef::Wait { ms } => action(Action::Wait(Duration::from_millis(ms))),
+            NodeDef::Check {
+                sensor,
+                below,
+                above,
+            } => {
+                if below.is_none() && above.is_none() {
+                    bail!("Check on {sensor:?} needs `below` or `above`");
+                }
+
+                Node::Check {
+                    sensor,
+                    below,
+                    above,
+                }
+            }
+        };
+
+        Ok(node)
+    }
+
+    /// Advances the node by one step. A node that returns Success or
+    /// Failure has reset itself and starts over on its next tick.
+    pub fn tick(&mut self, ctx: &mut Context) -> Status {
+        match self {
+            Node::Sequence { children, current } => {
+                run_in_order(children, current, Status::Success, ctx)
+            }
+            Node::Selector { children, current } => {
+                run_in_order(children, current, Status::Failure, ctx)
+            }
+            Node::Parallel {
+                children,
+                done,
+                success,
+            } => {
+                for (child, done) in children.iter_mut().zip(done.iter_mut()) {
+                    if done.is_none() {
+                        match child.tick(ctx) {
+                            Status::Running => {}
+                            status => *done = Some(status),
+                        }
+                    }
+                }
+
+                let succeeded = done.iter().filter(|s| **s == Some(Status::Success)).count();
+                let failed = done.iter().filter(|s| **s == Some(Status::Failure)).count();
+
+                let status = if succeeded >= *success {
+                    Status::Success
+                } else if children.len() - failed < *success {
+                    Status::Failure
+                } else {
+                    return Status::Running;
+                };
+
+                children.iter_mut().for_each(|child| child.halt(ctx));
+                done.iter_mut().for_each(|done| *done = None);
+                status
+            }
+            Node::Retry {
+                child,
+                times,
+                failures,
+            } => match child.tick(ctx) {
+                Status::Failure if *failures < *times => {
+                    *failures += 1;
+                    Status::Running
+                }
+                Status::Running => Status::Running,
+                status => {
+                    *failures = 0;
+                    status
+                }
+            },
+            Node::Repeat { child, times, runs } => match child.tick(ctx) {
+                Status::Success => {
+                    *runs += 1;
+
+                    if times.is_some_and(|times| *runs >= times) {
+                        *runs = 0;
+                        Status::Success
+                    } else {
+                        Status::Running
+                    }
+                }
+                Status::Failure => {
+                    *runs = 0;
+                    Status::Failure
+                }
+                Status::Running => Status::Running,
+            },
+            Node::Timeout {
+                child,
+                limit,
+                started,
+            } => {
+                let started_at = *started.get_or_insert(ctx.now);
+
+                if ctx.now.duration_since(started_at) >= *limit {
+                    child.halt(ctx);
+                    *started = None;
+                    return Status::Failure;
+                }
+
+                let status = child.tick(ctx);
+                if status != Status::Running {
+                    *started = None;
+                }
+
+                status
+            }
+            Node::Inverter { child } => match child.tick(ctx) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Guard { condition, child } => {
+                // A condition that's still running doesn't hold yet
+                if condition.tick(ctx) != Status::Success {
+                    condition.halt(ctx);
+                    child.halt(ctx);
+                    return Status::Failure;
+                }
+
+                child.tick(ctx)
+            }
+            Node::Action { action, started } => {
+                let first = started.is_none();
+                let started_at = *started.get_or_insert(ctx.now);
+                let elapsed = ctx.now.duration_since(started_at);
+
+                let status = action.tick(first, elapsed, ctx);
+                if status != Status::Running {
+                    *started = None;
+                }
+
+                status
+            }
+            Node::Check {
+                sensor,
+                below,
+                above,
+            } => {
+                let state = ctx.state;
+                let reading = match sensor {
+                    Sensor::Distance => state.ultrasound.as_ref().map(|u| u.distance),
+                    Sensor::LdrLeft => state.ldr.as_ref().map(|l| l.l_val as f64),
+                    Sensor::LdrMiddle => state.ldr.as_ref().map(|l| l.m_val as f64),
+                    Sensor::LdrRight => state.ldr.as_ref().map(|l| l.r_val as f64),
+                    Sensor::Servo => state.servo.as_ref().map(|s| s.angle as f64),
+                };
+
+                let passes = reading.is_some_and(|value| {
+                    below.is_none_or(|below| value < below)
+                        && above.is_none_or(|above| value > above)
+                });
+
+                if passes {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+        }
+    }
+
+    /// Abandons whatever the node is part way through, stopping the motors
+    /// if it was driving them for a set time
+    pub fn halt(&mut self, ctx: &mut Context) {
+        match self {
+            Node::Sequence { children, current } | Node::Selector { children, current } => {
+                children.iter_mut().for_each(|child| child.halt(ctx));
+                *current = 0;
+            }
+            Node::Parallel { children, done, .. } => {
+                children.iter_mut().for_each(|child| child.halt(ctx));
+                done.iter_mut().for_each(|done| *done = None);
+            }
+            Node::Retry {
+                child, failures, ..
+            } => {
+                child.halt(ctx);
+                *failures = 0;
+            }
+            Node::Repeat { child, runs, .. } => {
+                child.halt(ctx);
+                *runs = 0;
+            }
+            Node::Timeout { child, started, .. } => {
+                child.halt(ctx);
+                *started = None;
+            }
+            Node::Inverter { child } => child.halt(ctx),
+            Node::Guard { condition, child } => {
+                condition.halt(ctx);
+                child.halt(ctx);
+            }
+            Node::Action { action, started } => {
+                if started.take().is_some()
+                    && let Action::Move {
+                        duration: Some(_), ..
+                    } = action
+                {
+                    ctx.stop();
+                }
+            }
+            Node::Check { .. } => {}
+        }
+    }
+}
+
+impl Action {
+    fn tick(&self, first: bool, elapsed: Duration, ctx: &mut Context) -> Status {
+        match self {
+            Action::Move {
+                direction,
+                speed,
+                duration,
+            } => {
+                if first {
+                    ctx.drive(direction.clone(), *speed);
+                }
+
+                match duration {
+                    Some(duration) if elapsed < *duration => Status::Running,
+                    Some(_) => {
+                        ctx.stop();
+                        Status::Success
+                    }
+                    None => Status::Success,
+                }
+            }
+            Action::Stop => {
+                ctx.stop();
+                Status::Success
+            }
+            Action::Servo(angle) => {
+                ctx.publish(Event::ServoCommand(ServoCommand { angle: *angle }));
+                Status::Success
+            }
+            Action::Leds([red, green, blue], duration) => {
+                ctx.publish(Event::LedCommand(LedCommand {
+                    red: *red,
+                    green: *green,
+                    blue: *blue,
+                    duration: *duration,
+                }));
+                Status::Success
+            }
+            Action::Wait(duration) if elapsed < *duration => Status::Running,
+            Action::Wait(_) => Status::Success,
+        }
+    }
+}
+
+// Sequence and selector: tick children from `current` on, moving past any
+// that return `pass`, and stop at the first that doesn't
+fn run_in_order(
+    children: &mut [Node],
+    current: &mut usize,
+    pass: Status,
+    ctx: &mut Context,
+) -> Status {
+    while *current < children.len() {
+        match children[*current].tick(ctx) {
+            Status::Running => return Status::Running,
+            status if status == pass => *current += 1,
+            status => {
+                *current = 0;
+                return status;
+            }
+        }
+    }
+
+    *current = 0;
+    pass
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::testing::{motor, ms, run};
+    use crate::bus::{event::Ultrasound, state::RobotState};
+
+    fn tree(toml: &str) -> Node {
+        Node::build(toml::from_str(toml).unwrap()).unwrap()
+    }
+
+    fn at(distance: f64) -> RobotState {
+        RobotState {
+            ultrasound: Some(Ultrasound { distance }),
+            ..RobotState::default()
+        }
+    }
+
+    // Ticks `node` once, returning its status and what it published
+    fn tick(node: &mut Node, state: &RobotState, now: Instant) -> (Status, Vec<Event>) {
+        let mut status = Status::Running;
+        let events = run(state, now, |ctx| status = node.tick(ctx));
+        (status, events)
+    }
+
+    #[test]
+    fn sequence_runs_children_in_order() {
+        let mut node = tree(
+            r#"
+            type = "sequence"
+            children = [
+                { type = "drive", direction = "forward", speed = 50, ms = 100 },
+                { type = "wait", ms = 100 },
+                { type = "servo", angle = 45 },
+            ]
+            "#,
+        );
+        let state = RobotState::default();
+        let t0 = Instant::now();
+
+        let (status, events) = tick(&mut node, &state, t0);
+        assert_eq!(status, Status::Running);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Forward);
+
+        // The drive stops itself and the wait starts
+        let (status, events) = tick(&mut node, &state, t0 + ms(100));
+        assert_eq!(status, Status::Running);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Stop);
+
+        let (status, events) = tick(&mut node, &state, t0 + ms(200));
+        assert_eq!(status, Status::Success);
+        assert!(matches!(
+            events.as_slice(),
+            [Event::ServoCommand(ServoCommand { angle: 45 })]
+        ));
+    }
+
+    #[test]
+    fn selector_falls_through_failing_children() {
+        let mut node = tree(
+            r#"
+            type = "selector"
+            children = [
+                { type = "check", sensor = "distance", above = 20.0 },
+                { type = "stop" },
+            ]
+            "#,
+        );
+        let t0 = Instant::now();
+
+        let (status, events) = tick(&mut node, &at(50.0), t0);
+        assert_eq!(status, Status::Success);
+        assert!(events.is_empty());
+
+        let (status, events) = tick(&mut node, &at(10.0), t0);
+        assert_eq!(status, Status::Success);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Stop);
+
+        // No reading yet fails the check
+        let (_, events) = tick(&mut node, &RobotState::default(), t0);
+        assert!(motor(&events).is_some());
+    }
+
+    #[test]
+    fn guard_halts_its_child_when_the_condition_fails() {
+        let mut node = tree(
+            r#"
+            type = "guard"
+            condition = { type = "check", sensor = "distance", above = 20.0 }
+            child = { type = "drive", direction = "forward", ms = 2000 }
+            "#,
+        );
+        let t0 = Instant::now();
+
+        let (status, events) = tick(&mut node, &at(50.0), t0);
+        assert_eq!(status, Status::Running);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Forward);
+
+        let (status, events) = tick(&mut node, &at(40.0), t0 + ms(200));
+        assert_eq!(status, Status::Running);
+        assert!(events.is_empty());
+
+        let (status, events) = tick(&mut node, &at(15.0), t0 + ms(400));
+        assert_eq!(status, Status::Failure);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Stop);
+
+        // Starts the drive over once it holds again
+        let (status, events) = tick(&mut node, &at(50.0), t0 + ms(600));
+        assert_eq!(status, Status::Running);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Forward);
+    }
+
+    #[test]
+    fn parallel_succeeds_once_enough_children_have() {
+        let mut node = tree(
+            r#"
+            type = "parallel"
+            success = 1
+            children = [
+                { type = "wait", ms = 100 },
+                { type = "drive", direction = "left", ms = 500 },
+            ]
+            "#,
+        );
+        let state = RobotState::default();
+        let t0 = Instant::now();
+
+        let (status, _) = tick(&mut node, &state, t0);
+        assert_eq!(status, Status::Running);
+
+        // The unfinished drive is halted, which stops the motors
+        let (status, events) = tick(&mut node, &state, t0 + ms(100));
+        assert_eq!(status, Status::Success);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Stop);
+    }
+
+    #[test]
+    fn decorators_retry_repeat_and_time_out() {
+        let t0 = Instant::now();
+
+        let mut retry = tree(
+            r#"
+            type = "retry"
+            times = 2
+            child = { type = "check", sensor = "distance", below = 10.0 }
+            "#,
+        );
+        let state = at(50.0);
+        assert_eq!(tick(&mut retry, &state, t0).0, Status::Running);
+        assert_eq!(tick(&mut retry, &state, t0).0, Status::Running);
+        assert_eq!(tick(&mut retry, &state, t0).0, Status::Failure);
+
+        let mut repeat = tree(
+            r#"
+            type = "repeat"
+            times = 2
+            child = { type = "servo", angle = 90 }
+            "#,
+        );
+        assert_eq!(tick(&mut repeat, &state, t0).0, Status::Running);
+        assert_eq!(tick(&mut repeat, &state, t0).0, Status::Success);
+
+        let mut timeout = tree(
+            r#"
+            type = "timeout"
+            ms = 100
+            child = { type = "inverter", child = { type = "wait", ms = 500 } }
+            "#,
+        );
+        assert_eq!(tick(&mut timeout, &state, t0).0, Status::Running);
+        assert_eq!(tick(&mut timeout, &state, t0 + ms(100)).0, Status::Failure);
+    }
+
+    #[test]
+    fn rejects_invalid_trees() {
+        let wait = || NodeDef::Wait { ms: 100 };
+
+        assert!(Node::build(NodeDef::Sequence { children: vec![] }).is_err());
+        assert!(
+            Node::build(NodeDef::Parallel {
+                children: vec![wait(), wait()],
+                success: Some(3),
+            })
+            .is_err()
+        );
+        assert!(Node::build(NodeDef::Servo { angle: 200 }).is_err());
+        assert!(
+            Node::build(NodeDef::Check {
+                sensor: Sensor::Distance,
+                below: None,
+                above: None,
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn example_mission_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("mission.example.toml");
+
+        load(&path).unwrap();
+    }
+}
@@ -0,0 +1,442 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::{
+    behaviour::{Behaviour, Context},
+    bus::event::{Event, ServoCommand},
+    nodes::servo::{SERVO_CENTRE, ServoAxis, ServoConfig},
+};
+
+pub const NAME: &str = "wall-follow";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WallSide {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WallFollowConfig {
+    /// Side of the robot the wall is kept on
+    pub side: WallSide,
+    /// Distance to hold from the wall, cm
+    pub target_distance: f64,
+    /// Wheel speed with no steering, 0..=100
+    pub speed: u8,
+    /// Steering per cm off target
+    pub kp: f64,
+    /// Steering per cm/s of change in the distance
+    pub kd: f64,
+    /// Most the two sides' speeds may differ from `speed`
+    pub max_steer: f64,
+    /// How far the servo turns from centre to point the sensor at the wall
+    pub look_degrees: f64,
+    /// Time for the servo to turn before readings are looked at
+    pub settle_ms: u64,
+    /// The ultrasound readings are smoothed and take a while to catch up
+    /// after the servo turns. They're trusted once successive readings
+    /// differ by less than this, cm.
+    pub tolerance: f64,
+    /// Longest to wait for the readings to settle
+    pub max_settle_ms: u64,
+    /// How long to follow the wall before glancing ahead, once settled
+    pub side_ms: u64,
+    /// How long to look ahead for, once settled
+    pub ahead_ms: u64,
+    /// Anything closer than this ahead, cm, is turned away from
+    pub obstacle_distance: f64,
+    /// Spin away from the wall for this long when blocked ahead
+    pub turn_ms: u64,
+}
+
+impl Default for WallFollowConfig {
+    fn default() -> Self {
+        WallFollowConfig {
+            side: WallSide::Right,
+            target_distance: 20.0,
+            speed: 50,
+            kp: 2.0,
+            kd: 0.5,
+            max_steer: 40.0,
+            look_degrees: 80.0,
+            settle_ms: 250,
+            tolerance: 1.0,
+            max_settle_ms: 2500,
+            side_ms: 1000,
+            ahead_ms: 400,
+            obstacle_distance: 20.0,
+            turn_ms: 500,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Phase {
+    /// Sensor on the wall, steering to hold the target distance
+    Side,
+    /// Sensor pointing ahead, checking for obstacles
+    Ahead,
+    /// Spinning away from the wall around an obstacle
+    Turning,
+}
+
+/// Keeps a wall at a set distance on one side. The servo points the
+/// ultrasound sensor at the wall, a PD controller steers on the readings,
+/// and every so often the sensor glances ahead for obstacles.
+pub struct WallFollow {
+    config: WallFollowConfig,
+    servo: ServoConfig,
+    phase: Phase,
+    // When the phase started, or when its readings settled
+    since: Instant,
+    settled: bool,
+    // Previous reading while waiting to settle
+    last_reading: Option<f64>,
+    // Last wall reading's error and time, for the derivative term
+    last_error: Option<(f64, Instant)>,
+}
+
+impl WallFollow {
+    pub fn new(config: WallFollowConfig, servo: ServoConfig) -> Self {
+        WallFollow {
+            config,
+            servo,
+            phase: Phase::Side,
+            since: Instant::now(),
+            settled: false,
+            last_reading: None,
+            last_error: None,
+        }
+    }
+
+    // Servo angle pointing the sensor at the wall
+    fn wall_angle(&self) -> u8 {
+        let sign = match (self.config.side, self.servo.invert) {
+            (WallSide::Right, false) | (WallSide::Left, true) => 1.0,
+            _ => -1.0,
+        };
+
+        self.servo
+            .clamp(SERVO_CENTRE as f64 + sign * self.config.look_degrees)
+    }
+
+    fn enter(&mut self, phase: Phase, ctx: &mut Context) {
+        let c = &self.config;
+
+        match phase {
+            Phase::Side => {
+                // Straight on until the wall readings can be trusted again
+                ctx.wheels(c.speed as i8, c.speed as i8);
+                ctx.publish(Event::ServoCommand(ServoCommand {
+                    angle: self.wall_angle(),
+                }));
+                // The last reading is too old to take a rate from
+                self.last_error = None;
+            }
+            Phase::Ahead => {
+                // Glances while following keep driving straight on, but after
+                // a turn the robot waits to see whether the way is clear
+                if self.phase == Phase::Turning {
+                    ctx.wheels(0, 0);
+                } else {
+                    ctx.wheels(c.speed as i8, c.speed as i8);
+                }
+                ctx.publish(Event::ServoCommand(ServoCommand {
+                    angle: SERVO_CENTRE,
+                }));
+            }
+            Phase::Turning => {
+                let speed = c.speed as i8;
+                let (left, right) = match c.side {
+                    WallSide::Right => (-speed, speed),
+                    WallSide::Left => (speed, -speed),
+                };
+                ctx.wheels(left, right);
+            }
+        }
+
+        let reason = match (self.phase, phase) {
+            (_, Phase::Turning) => Some("obstacle ahead"),
+            (Phase::Turning, _) => Some("turned"),
+            _ => None,
+        };
+        if let Some(reason) = reason {
+            let name = |phase| match phase {
+                Phase::Turning => "turning",
+                _ => "following",
+            };
+            ctx.transition(NAME, Some(name(self.phase)), name(phase), reason);
+        }
+
+        self.phase = phase;
+        self.since = ctx.now;
+        // Turning doesn't use the sensor
+        self.settled = phase == Phase::Turning;
+        self.last_reading = None;
+    }
+
+    // Whether a reading can be used, like the scanner waiting at each step:
+    // once the servo has had time to turn, the readings are settled when
+    // one barely differs from the last. The phase's time starts from then.
+    fn settle(&mut self, distance: f64, now: Instant) -> bool {
+        if self.settled {
+            return true;
+        }
+
+        let elapsed = now.duration_since(self.since);
+        if elapsed < Duration::from_millis(self.config.settle_ms) {
+            return false;
+        }
+
+        let steady = self
+            .last_reading
+            .is_some_and(|last| (distance - last).abs() < self.config.tolerance);
+        self.last_reading = Some(distance);
+
+        if steady || elapsed >= Duration::from_millis(self.config.max_settle_ms) {
+            self.settled = true;
+            self.since = now;
+        }
+
+        self.settled
+    }
+
+    // PD on the distance to the wall, steering towards it when too far
+    fn steer(&mut self, distance: f64, ctx: &mut Context) {
+        let c = &self.config;
+        let error = distance - c.target_distance;
+
+        let rate = self.last_error.map_or(0.0, |(last, at)| {
+            let dt = ctx.now.duration_since(at).as_secs_f64();
+            if dt > 0.0 { (error - last) / dt } else { 0.0 }
+        });
+        self.last_error = Some((error, ctx.now));
+
+        let steer = (c.kp * error + c.kd * rate).clamp(-c.max_steer, c.max_steer);
+        let toward = match c.side {
+            WallSide::Right => steer,
+            WallSide::Left => -steer,
+        };
+        let speed = c.speed as f64;
+
+        ctx.wheels(
+            (speed + toward).clamp(-100.0, 100.0) as i8,
+            (speed - toward).clamp(-100.0, 100.0) as i8,
+        );
+    }
+}
+
+impl Behaviour for WallFollow {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn on_enter(&mut self, ctx: &mut Context) {
+        self.last_error = None;
+
+        if self.servo.axis != ServoAxis::Pan {
+            eprintln!("[{NAME}] needs the ultrasound on a pan servo, see [servo] axis");
+            ctx.transition(NAME, None, "failed", "servo is not a pan servo");
+            return;
+        }
+
+        self.phase = Phase::Side;
+        self.enter(Phase::Side, ctx);
+        ctx.transition(NAME, None, "following", "started");
+    }
+
+    fn on_event(&mut self, event: &Event, ctx: &mut Context) {
+        let Event::Ultrasound(ultrasound) = event else {
+            return;
+        };
+        if self.servo.axis != ServoAxis::Pan || !self.settle(ultrasound.distance, ctx.now) {
+            return;
+        }
+
+        match self.phase {
+            Phase::Side => self.steer(ultrasound.distance, ctx),
+            Phase::Ahead if ultrasound.distance < self.config.obstacle_distance => {
+                self.enter(Phase::Turning, ctx)
+            }
+            _ => {}
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &mut Context) {
+        if self.servo.axis != ServoAxis::Pan {
+            return;
+        }
+
+        let c = &self.config;
+        let elapsed = ctx.now.duration_since(self.since);
+
+        // Readings that never settle, or never come, are waited out
+        if !self.settled {
+            if elapsed >= Duration::from_millis(c.max_settle_ms) {
+                self.settled = true;
+                self.since = ctx.now;
+            }
+            return;
+        }
+
+        let next = match self.phase {
+            Phase::Side if elapsed >= Duration::from_millis(c.side_ms) => Phase::Ahead,
+            Phase::Ahead if elapsed >= Duration::from_millis(c.ahead_ms) => Phase::Side,
+            Phase::Turning if elapsed >= Duration::from_millis(c.turn_ms) => Phase::Ahead,
+            _ => return,
+        };
+
+        self.enter(next, ctx);
+    }
+
+    fn on_exit(&mut self, ctx: &mut Context) {
+        ctx.publish(Event::ServoCommand(ServoCommand {
+            angle: SERVO_CENTRE,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behaviour::testing::{ms, reading, run, transitions, wheels};
+    use crate::bus::{event::WheelCommand, state::RobotState};
+
+    fn servo_angle(events: &[Event]) -> Option<u8> {
+        events.iter().rev().find_map(|event| match event {
+            Event::ServoCommand(cmd) => Some(cmd.angle),
+            _ => None,
+        })
+    }
+
+    // Wall-follow on the right, started at `t0`, with the readings about to
+    // settle at 30 cm
+    fn settled(t0: Instant) -> (WallFollow, Instant) {
+        let state = RobotState::default();
+        let mut follow = WallFollow::new(WallFollowConfig::default(), ServoConfig::default());
+
+        let events = run(&state, t0, |ctx| follow.on_enter(ctx));
+        assert_eq!(transitions(&events), ["following"]);
+        assert_eq!(servo_angle(&events), Some(170));
+        assert_eq!(
+            wheels(&events),
+            Some(WheelCommand {
+                left: 50,
+                right: 50
+            })
+        );
+
+        // The servo is still turning, then the smoothed readings catch up
+        let mut t = t0 + ms(100);
+        for distance in [80.0, 60.0, 40.0, 30.5] {
+            let events = run(&state, t, |ctx| follow.on_event(&reading(distance), ctx));
+            assert!(events.is_empty(), "steered at {distance} before settling");
+            t += ms(200);
+        }
+
+        (follow, t)
+    }
+
+    #[test]
+    fn needs_a_pan_servo() {
+        let servo = ServoConfig {
+            axis: ServoAxis::Tilt,
+            ..ServoConfig::default()
+        };
+        let mut follow = WallFollow::new(WallFollowConfig::default(), servo);
+
+        let events = run(&RobotState::default(), Instant::now(), |ctx| {
+            follow.on_enter(ctx)
+        });
+        assert_eq!(transitions(&events), ["failed"]);
+    }
+
+    #[test]
+    fn steers_on_the_settled_readings() {
+        let state = RobotState::default();
+        let (mut follow, t) = settled(Instant::now());
+
+        // 10 cm too far: steer right, towards the wall, by kp * 10
+        let events = run(&state, t, |ctx| follow.on_event(&reading(30.0), ctx));
+        assert_eq!(
+            wheels(&events),
+            Some(WheelCommand {
+                left: 70,
+                right: 30
+            })
+        );
+
+        // Closing in at 26 cm/s: the derivative steers back out
+        let t = t + ms(200);
+        let events = run(&state, t, |ctx| follow.on_event(&reading(25.0), ctx));
+        let WheelCommand { left, right } = wheels(&events).unwrap();
+        assert!(left < right, "still steering in at {left}/{right}");
+
+        // Far off the wall the steering is limited
+        let t = t + ms(200);
+        let events = run(&state, t, |ctx| follow.on_event(&reading(150.0), ctx));
+        assert_eq!(
+            wheels(&events),
+            Some(WheelCommand {
+                left: 90,
+                right: 10
+            })
+        );
+    }
+
+    #[test]
+    fn turns_away_from_a_settled_obstacle_ahead() {
+        let state = RobotState::default();
+        let config = WallFollowConfig::default();
+        let (mut follow, t) = settled(Instant::now());
+        run(&state, t, |ctx| follow.on_event(&reading(30.0), ctx));
+
+        let t = t + ms(config.side_ms);
+        let events = run(&state, t, |ctx| follow.on_tick(ctx));
+        assert_eq!(servo_angle(&events), Some(SERVO_CENTRE));
+
+        // The first close readings are still the wall, smoothed
+        let mut t = t + ms(config.settle_ms);
+        for distance in [25.0, 15.0, 12.0] {
+            let events = run(&state, t, |ctx| follow.on_event(&reading(distance), ctx));
+            assert!(transitions(&events).is_empty());
+            t += ms(200);
+        }
+
+        let events = run(&state, t, |ctx| follow.on_event(&reading(11.5), ctx));
+        assert_eq!(transitions(&events), ["turning"]);
+        assert_eq!(
+            wheels(&events),
+            Some(WheelCommand {
+                left: -50,
+                right: 50
+            })
+        );
+
+        // Then stops to look again
+        let events = run(&state, t + ms(config.turn_ms), |ctx| follow.on_tick(ctx));
+        assert_eq!(transitions(&events), ["following"]);
+        assert_eq!(wheels(&events), Some(WheelCommand { left: 0, right: 0 }));
+    }
+
+    #[test]
+    fn carries_on_without_readings() {
+        let state = RobotState::default();
+        let config = WallFollowConfig::default();
+        let t0 = Instant::now();
+        let mut follow = WallFollow::new(config.clone(), ServoConfig::default());
+        run(&state, t0, |ctx| follow.on_enter(ctx));
+
+        let settled = t0 + ms(config.max_settle_ms);
+        let events = run(&state, settled, |ctx| follow.on_tick(ctx));
+        assert!(events.is_empty());
+
+        let events = run(&state, settled + ms(config.side_ms), |ctx| {
+            follow.on_tick(ctx)
+        });
+        assert_eq!(servo_angle(&events), Some(SERVO_CENTRE));
+    }
+}
@@ -0,0 +1,515 @@
+use std::time::{Duration, Instant};
+
+use rand::seq::IndexedRandom;
+use serde::Deserialize;
+
+use crate::behaviour::{Behaviour, Context};
+use crate::bus::event::{Event, MotorDirection, RangeScan};
+use crate::nodes::servo::ServoAxis;
+
+pub const NAME: &str = "wander";
+
+// Readings this far off are out of the sensor's range and never change,
+// so they say nothing about whether the robot is moving
+const MAX_RANGE: f64 = 200.0;
+
+// Readings have to move by more than this, in cm, to count as progress
+const PROGRESS_DELTA: f64 = 2.0;
+
+// Extra distance, in cm, before Approach speeds back up to Cruise
+const HYSTERESIS: f64 = 5.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WanderConfig {
+    /// Slow down when something is closer than this, cm
+    pub approach_distance: f64,
+    /// Turn away when something is closer than this, cm
+    pub avoid_distance: f64,
+    /// A turn has found a way out once the reading is at least this, cm
+    pub clear_distance: f64,
+    pub cruise_speed: u8,
+    pub approach_speed: u8,
+    pub turn_speed: u8,
+    pub reverse_speed: u8,
+    /// How long each avoidance turn lasts before checking the way is clear
+    pub turn_ms: u64,
+    /// Turns tried before backing out
+    pub max_turns: u32,
+    /// How long to reverse for, standing in for a fixed distance
+    pub reverse_ms: u64,
+    /// Longer turn after reversing, to face somewhere new
+    pub recover_ms: u64,
+    /// Reverse-and-recover rounds before giving up as stuck
+    pub max_recoveries: u32,
+    /// Driving with no change in distance for this long means the wheels
+    /// are blocked by something the sensor can't see
+    pub no_progress_secs: f64,
+    /// Time to sit still when stuck before trying again
+    pub stuck_secs: f64,
+    /// Sweep the ultrasound before turning away from an obstacle and turn
+    /// towards the widest gap, rather than picking a side at random. Only
+    /// used with the sensor on a pan servo (`[servo] axis = "pan"`).
+    pub scan: bool,
+    /// Turn a random way if the scan hasn't come back after this long
+    pub scan_timeout_secs: f64,
+}
+
+impl Default for WanderConfig {
+    fn default() -> Self {
+        WanderConfig {
+            approach_distance: 30.0,
+            avoid_distance: 10.0,
+            clear_distance: 20.0,
+            cruise_speed: 100,
+            approach_speed: 60,
+            turn_speed: 100,
+            reverse_speed: 90,
+            turn_ms: 600,
+            max_turns: 4,
+            reverse_ms: 800,
+            recover_ms: 1200,
+            max_recoveries: 2,
+            no_progress_secs: 3.0,
+            stuck_secs: 30.0,
+            scan: true,
+            scan_timeout_secs: 30.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn random() -> Self {
+        *[Side::Left, Side::Right].choose(&mut rand::rng()).unwrap()
+    }
+
+    // Side of the widest gap in `scan`, if there is one
+    fn towards_gap(scan: &RangeScan, clear: f64) -> Option<Self> {
+        scan.widest_gap(clear).map(|bearing| {
+            if bearing < 0.0 {
+                Side::Left
+            } else {
+                Side::Right
+            }
+        })
+    }
+
+    fn direction(self) -> MotorDirection {
+        match self {
+            Side::Left => MotorDirection::Left,
+            Side::Right => MotorDirection::Right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Driving {
+    Cruise,
+    Approach,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Avoiding {
+    /// Stopped while the scanner sweeps for a way out
+    Scan,
+    Turn(Side),
+    Reverse,
+    Recover(Side),
+}
+
+/// Two superstates, each handling what applies to all of its substates
+/// before handing over to them, plus Stuck on its own
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Driving(Driving),
+    Avoiding(Avoiding),
+    Stuck,
+}
+
+impl State {
+    fn name(self) -> &'static str {
+        match self {
+            State::Driving(Driving::Cruise) => "driving/cruise",
+            State::Driving(Driving::Approach) => "driving/approach",
+            State::Avoiding(Avoiding::Scan) => "avoiding/scan",
+            State::Avoiding(Avoiding::Turn(_)) => "avoiding/turn",
+            State::Avoiding(Avoiding::Reverse) => "avoiding/reverse",
+            State::Avoiding(Avoiding::Recover(_)) => "avoiding/recover",
+            State::Stuck => "stuck",
+        }
+    }
+}
+
+/// Roams forward and steers clear of obstacles, as a hierarchical state
+/// machine. Every avoidance state has a timed exit, so the robot re-checks
+/// the way ahead rather than turning forever.
+pub struct Wander {
+    config: WanderConfig,
+    // Scans need the sensor on a pan servo
+    axis: ServoAxis,
+    state: State,
+    entered: Instant,
+    distance: f64,
+    // Reading and time of the last change in distance while driving
+    progress: (f64, Instant),
+    turns: u32,
+    recoveries: u32,
+    // Scan that came back while in Avoiding::Scan
+    scan: Option<RangeScan>,
+}
+
+impl Wander {
+    pub fn new(config: WanderConfig, axis: ServoAxis) -> Self {
+        let now = Instant::now();
+
+        Wander {
+            config,
+            axis,
+            state: State::Driving(Driving::Cruise),
+            entered: now,
+            distance: MAX_RANGE,
+            progress: (MAX_RANGE, now),
+            turns: 0,
+            recoveries: 0,
+            scan: None,
+        }
+    }
+
+    // Runs the entry action of `next`. `from` is None when the behaviour
+    // has just been selected.
+    fn enter(&mut self, from: Option<State>, next: State, reason: &'static str, ctx: &mut Context) {
+        if matches!(next, State::Avoiding(_)) && !matches!(from, Some(State::Avoiding(_))) {
+            self.turns = 0;
+            self.recoveries = 0;
+        }
+        if matches!(next, State::Driving(_)) && !matches!(from, Some(State::Driving(_))) {
+            self.progress = (self.distance, ctx.now);
+        }
+
+        self.state = next;
+        self.entered = ctx.now;
+
+        let c = &self.config;
+        match next {
+            State::Driving(Driving::Cruise) => ctx.drive(MotorDirection::Forward, c.cruise_speed),
+            State::Driving(Driving::Approach) => {
+                ctx.drive(MotorDirection::Forward, c.approach_speed)
+            }
+            State::Avoiding(Avoiding::Turn(side) | Avoiding::Recover(side)) => {
+                ctx.drive(side.direction(), c.turn_speed)
+            }
+            State::Avoiding(Avoiding::Scan) => {
+                self.scan = None;
+                ctx.stop();
+                ctx.publish(Event::ScanCommand);
+            }
+            State::Avoiding(Avoiding::Reverse) => {
+                ctx.drive(MotorDirection::Backward, c.reverse_speed)
+            }
+            State::Stuck => ctx.stop(),
+        }
+
+        println!("[{NAME}] {} ({reason})", next.name());
+        ctx.transition(NAME, from.map(State::name), next.name(), reason);
+    }
+
+    fn elapsed(&self, now: Instant, ms: u64) -> bool {
+        now.duration_since(self.entered) >= Duration::from_millis(ms)
+    }
+
+    fn scanning(&self) -> bool {
+        self.config.scan && self.axis == ServoAxis::Pan
+    }
+
+    fn clear(&self) -> bool {
+        self.distance >= self.config.clear_distance
+    }
+
+    fn driving(&mut self, sub: Driving, now: Instant) -> Option<(State, &'static str)> {
+        let c = &self.config;
+
+        if self.distance < c.avoid_distance {
+            let next = if self.scanning() {
+                Avoiding::Scan
+            } else {
+                Avoiding::Turn(Side::random())
+            };
+            return Some((State::Avoiding(next), "obstacle"));
+        }
+
+        if self.distance < MAX_RANGE && (self.distance - self.progress.0).abs() > PROGRESS_DELTA {
+            self.progress = (self.distance, now);
+        }
+        if self.distance < MAX_RANGE
+            && now.duration_since(self.progress.1).as_secs_f64() >= c.no_progress_secs
+        {
+            return Some((State::Avoiding(Avoiding::Reverse), "no progress"));
+        }
+
+        match sub {
+            Driving::Cruise if self.distance < c.approach_distance => {
+                Some((State::Driving(Driving::Approach), "closing in"))
+            }
+            Driving::Approach if self.distance > c.approach_distance + HYSTERESIS => {
+                Some((State::Driving(Driving::Cruise), "open space"))
+            }
+            _ => None,
+        }
+    }
+
+    fn avoiding(&mut self, sub: Avoiding, now: Instant) -> Option<(State, &'static str)> {
+        let c = &self.config;
+
+        match sub {
+            Avoiding::Scan => match self.scan.take() {
+                Some(scan) => match Side::towards_gap(&scan, c.clear_distance) {
+                    Some(side) => Some((State::Avoiding(Avoiding::Turn(side)), "widest gap")),
+                    None => Some((State::Avoiding(Avoiding::Reverse), "no gap")),
+                },
+                None if now.duration_since(self.entered).as_secs_f64() >= c.scan_timeout_secs => {
+                    Some((
+                        State::Avoiding(Avoiding::Turn(Side::random())),
+                        "scan timed out",
+                    ))
+                }
+                None => None,
+            },
+            Avoiding::Turn(side) if self.elapsed(now, c.turn_ms) => {
+                if self.clear() {
+                    return Some((State::Driving(Driving::Cruise), "clear"));
+                }
+
+                self.turns += 1;
+                if self.turns >= c.max_turns {
+                    Some((State::Avoiding(Avoiding::Reverse), "boxed in"))
+                } else {
+                    Some((State::Avoiding(Avoiding::Turn(side)), "still blocked"))
+                }
+            }
+            Avoiding::Reverse if self.elapsed(now, c.reverse_ms) => Some((
+                State::Avoiding(Avoiding::Recover(Side::random())),
+                "reversed",
+            )),
+            Avoiding::Recover(_) if self.elapsed(now, c.recover_ms) => {
+                if self.clear() {
+                    return Some((State::Driving(Driving::Cruise), "clear"));
+                }
+
+                self.recoveries += 1;
+                if self.recoveries >= c.max_recoveries {
+                    Some((State::Stuck, "no way out"))
+                } else {
+                    Some((State::Avoiding(Avoiding::Reverse), "still blocked"))
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Behaviour for Wander {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn on_enter(&mut self, ctx: &mut Context) {
+        *self = Wander::new(self.config.clone(), self.axis);
+
+        if self.config.scan && !self.scanning() {
+            eprintln!("[{NAME}] scan needs the ultrasound on a pan servo, turning at random");
+        }
+
+        self.enter(None, State::Driving(Driving::Cruise), "started", ctx);
+    }
+
+    fn on_event(&mut self, event: &Event, _ctx: &mut Context) {
+        match event {
+            Event::Ultrasound(ultrasound) => self.distance = ultrasound.distance,
+            Event::RangeScan(scan) if self.state == State::Avoiding(Avoiding::Scan) => {
+                self.scan = Some(scan.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn on_tick(&mut self, ctx: &mut Context) {
+        let next = match self.state {
+            State::Driving(sub) => self.driving(sub, ctx.now),
+            State::Avoiding(sub) => self.avoiding(sub, ctx.now),
+            State::Stuck => {
+                let wait = Duration::from_secs_f64(self.config.stuck_secs);
+
+                (ctx.now.duration_since(self.entered) >= wait)
+                    .then_some((State::Avoiding(Avoiding::Reverse), "retrying"))
+            }
+        };
+
+        if let Some((next, reason)) = next {
+            self.enter(Some(self.state), next, reason, ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::behaviour::testing::{motor, ms, reading, reason, run, transitions};
+    use crate::bus::state::RobotState;
+
+    // Wander started at `t0`, without scanning unless the servo pans
+    fn started(config: WanderConfig, axis: ServoAxis, t0: Instant) -> Wander {
+        let mut wander = Wander::new(config, axis);
+        let events = run(&RobotState::default(), t0, |ctx| wander.on_enter(ctx));
+
+        assert_eq!(transitions(&events), ["driving/cruise"]);
+        wander
+    }
+
+    fn turning(events: &[Event]) -> bool {
+        motor(events).is_some_and(|cmd| {
+            matches!(cmd.direction, MotorDirection::Left | MotorDirection::Right)
+        })
+    }
+
+    #[test]
+    fn turns_away_from_an_obstacle_and_drives_on_once_clear() {
+        let state = RobotState::default();
+        let config = WanderConfig::default();
+        let t0 = Instant::now();
+        let mut wander = started(config.clone(), ServoAxis::Tilt, t0);
+
+        run(&state, t0, |ctx| wander.on_event(&reading(25.0), ctx));
+        let events = run(&state, t0 + ms(200), |ctx| wander.on_tick(ctx));
+        assert_eq!(transitions(&events), ["driving/approach"]);
+
+        run(&state, t0, |ctx| wander.on_event(&reading(5.0), ctx));
+        let events = run(&state, t0 + ms(400), |ctx| wander.on_tick(ctx));
+        assert_eq!(transitions(&events), ["avoiding/turn"]);
+        assert!(turning(&events));
+
+        // The turn runs its full time before looking again
+        run(&state, t0, |ctx| wander.on_event(&reading(50.0), ctx));
+        let events = run(&state, t0 + ms(800), |ctx| wander.on_tick(ctx));
+        assert!(events.is_empty());
+
+        let events = run(&state, t0 + ms(400 + config.turn_ms), |ctx| {
+            wander.on_tick(ctx)
+        });
+        assert_eq!(transitions(&events), ["driving/cruise"]);
+        assert_eq!(reason(&events), Some("clear"));
+    }
+
+    #[test]
+    fn boxed_in_reverses_then_gets_stuck_and_retries() {
+        let state = RobotState::default();
+        let config = WanderConfig {
+            max_turns: 2,
+            max_recoveries: 1,
+            ..WanderConfig::default()
+        };
+        let t0 = Instant::now();
+        let mut wander = started(config.clone(), ServoAxis::Tilt, t0);
+        run(&state, t0, |ctx| wander.on_event(&reading(5.0), ctx));
+
+        let mut tick = |at: Instant| transitions(&run(&state, at, |ctx| wander.on_tick(ctx)));
+        let mut t = t0;
+        assert_eq!(tick(t), ["avoiding/turn"]);
+
+        t += ms(config.turn_ms);
+        assert_eq!(tick(t), ["avoiding/turn"]);
+        t += ms(config.turn_ms);
+        assert_eq!(tick(t), ["avoiding/reverse"]);
+        t += ms(config.reverse_ms);
+        assert_eq!(tick(t), ["avoiding/recover"]);
+        t += ms(config.recover_ms);
+        assert_eq!(tick(t), ["stuck"]);
+
+        t += Duration::from_secs_f64(config.stuck_secs) - ms(1);
+        assert!(tick(t).is_empty());
+        t += ms(1);
+        assert_eq!(tick(t), ["avoiding/reverse"]);
+    }
+
+    #[test]
+    fn reverses_when_the_distance_stops_changing() {
+        let state = RobotState::default();
+        let config = WanderConfig::default();
+        let t0 = Instant::now();
+        let mut wander = started(config.clone(), ServoAxis::Tilt, t0);
+
+        run(&state, t0, |ctx| wander.on_event(&reading(80.0), ctx));
+        let events = run(&state, t0 + ms(200), |ctx| wander.on_tick(ctx));
+        assert!(events.is_empty());
+
+        // Progress was last made when the reading changed, on the first tick
+        let stalled = t0 + ms(200) + Duration::from_secs_f64(config.no_progress_secs);
+        let events = run(&state, stalled, |ctx| wander.on_tick(ctx));
+        assert_eq!(transitions(&events), ["avoiding/reverse"]);
+        assert_eq!(reason(&events), Some("no progress"));
+    }
+
+    #[test]
+    fn scans_and_turns_towards_the_widest_gap() {
+        let state = RobotState::default();
+        let t0 = Instant::now();
+        let mut wander = started(WanderConfig::default(), ServoAxis::Pan, t0);
+
+        run(&state, t0, |ctx| wander.on_event(&reading(5.0), ctx));
+        let events = run(&state, t0, |ctx| wander.on_tick(ctx));
+        assert_eq!(transitions(&events), ["avoiding/scan"]);
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Stop);
+        assert!(events.iter().any(|e| matches!(e, Event::ScanCommand)));
+
+        // Open on the right only
+        let scan = RangeScan {
+            angles: vec![50, 90, 130],
+            bearings: vec![-40.0, 0.0, 40.0],
+            distances: vec![8.0, 8.0, 90.0],
+            timestamp: Utc::now(),
+        };
+        run(&state, t0, |ctx| {
+            wander.on_event(&Event::RangeScan(scan), ctx)
+        });
+        let events = run(&state, t0 + ms(200), |ctx| wander.on_tick(ctx));
+        assert_eq!(transitions(&events), ["avoiding/turn"]);
+        assert_eq!(reason(&events), Some("widest gap"));
+        assert_eq!(motor(&events).unwrap().direction, MotorDirection::Right);
+    }
+
+    #[test]
+    fn turns_anyway_when_the_scan_never_comes_back() {
+        let state = RobotState::default();
+        let config = WanderConfig::default();
+        let t0 = Instant::now();
+        let mut wander = started(config.clone(), ServoAxis::Pan, t0);
+
+        run(&state, t0, |ctx| wander.on_event(&reading(5.0), ctx));
+        run(&state, t0, |ctx| wander.on_tick(ctx));
+
+        let timeout = t0 + Duration::from_secs_f64(config.scan_timeout_secs);
+        let events = run(&state, timeout, |ctx| wander.on_tick(ctx));
+        assert_eq!(transitions(&events), ["avoiding/turn"]);
+        assert_eq!(reason(&events), Some("scan timed out"));
+        assert!(turning(&events));
+    }
+
+    #[test]
+    fn skips_the_scan_without_a_pan_servo() {
+        let state = RobotState::default();
+        let t0 = Instant::now();
+        let mut wander = started(WanderConfig::default(), ServoAxis::Tilt, t0);
+
+        run(&state, t0, |ctx| wander.on_event(&reading(5.0), ctx));
+        let events = run(&state, t0, |ctx| wander.on_tick(ctx));
+        assert_eq!(transitions(&events), ["avoiding/turn"]);
+        assert!(!events.iter().any(|e| matches!(e, Event::ScanCommand)));
+    }
+}
@@ -1,4 +1,7 @@
-use serde::Serialize;
+use std::{fmt, time::Duration};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum MotorDirection {
@@ -15,16 +18,62 @@ pub struct MotorCommand {
     pub speed: u8,
 }
 
+impl MotorCommand {
+    /// The same command as a speed for each side
+    pub fn wheels(&self) -> WheelCommand {
+        let speed = self.speed.min(100) as i8;
+        let (left, right) = match self.direction {
+            MotorDirection::Forward => (speed, speed),
+            MotorDirection::Backward => (-speed, -speed),
+            MotorDirection::Left => (-speed, speed),
+            MotorDirection::Right => (speed, -speed),
+            MotorDirection::Stop => (0, 0),
+        };
+
+        WheelCommand { left, right }
+    }
+}
+
+/// Sets each side's wheels separately, -100..=100 with negative meaning
+/// backwards. Lets behaviours steer while driving rather than only spin.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct WheelCommand {
+    pub left: i8,
+    pub right: i8,
+}
+
+/// Measured from the wheel encoders, positive forwards
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct WheelSpeed {
+    /// Encoder edges counted since start
+    pub left_ticks: i64,
+    pub right_ticks: i64,
+    /// cm/s
+    pub left: f64,
+    pub right: f64,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct ServoCommand {
     pub angle: u8,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Mode {
+    #[default]
     Manual,
-    Automatic,
+    /// Driven by the named behaviour, see `behaviour::REGISTRY`
+    Automatic(&'static str),
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Mode::Manual => write!(f, "manual"),
+            Mode::Automatic(behaviour) => write!(f, "{behaviour}"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -32,6 +81,17 @@ pub struct ModeCommand {
     pub mode: Mode,
 }
 
+#[derive(Debug, Clone)]
+pub struct RecordCommand {
+    pub recording: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Recording {
+    pub recording: bool,
+    pub segment: Option<String>,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct Ultrasound {
     pub distance: f64,
@@ -52,16 +112,247 @@ pub struct Led {
     pub brightness: u8,
 }
 
+/// Flashes the neopixels in one colour, after which they go back to
+/// showing distance
+#[derive(Debug, Clone)]
+pub struct LedCommand {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BoundingBox {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Largest region of the tracked colour in a camera frame, in pixels
+#[derive(Debug, Serialize, Clone)]
+pub struct Blob {
+    pub label: String,
+    pub cx: i32,
+    pub cy: i32,
+    pub area: f64,
+    pub bbox: BoundingBox,
+    pub frame_width: i32,
+    pub frame_height: i32,
+}
+
+/// Marker position relative to the camera, in the units of the configured
+/// marker size. x is right, y is down and z is out along the optical axis.
+#[derive(Debug, Serialize, Clone)]
+pub struct Pose {
+    pub translation: [f64; 3],
+    /// Rodrigues rotation vector, radians
+    pub rotation: [f64; 3],
+    pub distance: f64,
+    /// Horizontal angle from the optical axis, degrees, positive to the right
+    pub bearing: f64,
+}
+
+/// ArUco or AprilTag marker found in a camera frame
+#[derive(Debug, Serialize, Clone)]
+pub struct Fiducial {
+    pub id: i32,
+    /// Corner pixels, clockwise from the marker's top left
+    pub corners: [[f32; 2]; 4],
+    /// Only estimated when a camera calibration is configured
+    pub pose: Option<Pose>,
+    pub frame_width: i32,
+    pub frame_height: i32,
+}
+
+/// Foreground movement against the learned background, in pixels
+#[derive(Debug, Serialize, Clone)]
+pub struct Motion {
+    /// Fraction of the frame that changed, 0.0..=1.0
+    pub fraction: f64,
+    /// Box around the largest moving region
+    pub bbox: BoundingBox,
+    pub cx: i32,
+    pub cy: i32,
+    pub frame_width: i32,
+    pub frame_height: i32,
+}
+
+/// Decoded QR code in a camera frame
+#[derive(Debug, Serialize, Clone)]
+pub struct QrCode {
+    pub text: String,
+    pub bbox: BoundingBox,
+    pub frame_width: i32,
+    pub frame_height: i32,
+}
+
+/// Largest face in a camera frame, in pixels
+#[derive(Debug, Serialize, Clone)]
+pub struct Face {
+    pub bbox: BoundingBox,
+    pub cx: i32,
+    pub cy: i32,
+    /// Number of faces in view
+    pub faces: usize,
+    pub frame_width: i32,
+    pub frame_height: i32,
+}
+
+/// Ultrasound distances from one sweep of the servo, one per step, in the
+/// order they were taken
+#[derive(Debug, Serialize, Clone)]
+pub struct RangeScan {
+    /// Servo angle of each reading
+    pub angles: Vec<u8>,
+    /// Direction of each reading from straight ahead, degrees, positive to
+    /// the right
+    pub bearings: Vec<f64>,
+    /// cm
+    pub distances: Vec<f64>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl RangeScan {
+    /// Bearing through the middle of the widest run of readings at least
+    /// `clear` cm away. Ties go to the run nearest straight ahead.
+    pub fn widest_gap(&self, clear: f64) -> Option<f64> {
+        let mut best: Option<(usize, f64)> = None;
+        let mut start = None;
+
+        for i in 0..=self.distances.len() {
+            let open = self.distances.get(i).is_some_and(|&d| d >= clear);
+
+            match (open, start) {
+                (true, None) => start = Some(i),
+                (false, Some(first)) => {
+                    let width = i - first;
+                    let bearing = (self.bearings[first] + self.bearings[i - 1]) / 2.0;
+                    let better = best.is_none_or(|(best_width, best_bearing)| {
+                        width > best_width
+                            || (width == best_width && bearing.abs() < best_bearing.abs())
+                    });
+                    if better {
+                        best = Some((width, bearing));
+                    }
+                    start = None;
+                }
+                _ => {}
+            }
+        }
+
+        best.map(|(_, bearing)| bearing)
+    }
+}
+
+/// Dead-reckoned robot position, cm from where it started or was last
+/// reset, facing `theta` radians anticlockwise from its starting heading.
+/// x is forward at the start and y is to the left.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RobotPose {
+    pub x: f64,
+    pub y: f64,
+    pub theta: f64,
+    /// Uncertainty of (x, y, theta), growing the further the robot drives
+    pub covariance: [[f64; 3]; 3],
+}
+
+/// How fast the robot goes at full speed, measured by driving it for a
+/// known time. Speed is taken to scale with the command below that.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OdometryCalibration {
+    /// Forward speed, cm/s
+    pub speed_cm_s: f64,
+    /// Spin rate on the spot, degrees/s
+    pub turn_deg_s: f64,
+}
+
+/// Move between states inside the running behaviour, e.g. wander going
+/// from `driving/cruise` to `avoiding/turn`
+#[derive(Debug, Serialize, Clone)]
+pub struct BehaviourTransition {
+    pub behaviour: &'static str,
+    /// None when the behaviour has just started
+    pub from: Option<&'static str>,
+    pub to: &'static str,
+    pub reason: &'static str,
+}
+
 #[derive(Debug, Clone)]
 pub enum Event {
     MotorCommand(MotorCommand),
+    WheelCommand(WheelCommand),
     ServoCommand(ServoCommand),
     ModeCommand(ModeCommand),
+    /// Asks the scanner to sweep the ultrasound across the servo's range
+    ScanCommand,
+    /// Puts the odometry pose back at the origin
+    OdometryReset,
+    /// New coefficients for the odometry model, just measured
+    OdometryCalibration(OdometryCalibration),
+    RecordCommand(RecordCommand),
+    LedCommand(LedCommand),
+    Recording(Recording),
     Ultrasound(Ultrasound),
+    WheelSpeed(WheelSpeed),
     Ldr(Ldr),
-    #[allow(dead_code)]
     Led(Led),
-    #[allow(dead_code)]
     Servo(ServoCommand),
+    BlobDetected(Blob),
+    FiducialDetected(Fiducial),
+    MotionDetected(Motion),
+    QrCodeDetected(QrCode),
+    FaceDetected(Face),
+    RangeScan(RangeScan),
+    Pose(RobotPose),
+    BehaviourTransition(BehaviourTransition),
     Shutdown,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(distances: &[f64]) -> RangeScan {
+        // Readings 20° apart, centred on straight ahead
+        let middle = distances.len() as f64 / 2.0 - 0.5;
+
+        RangeScan {
+            angles: (0..distances.len()).map(|i| 10 + 20 * i as u8).collect(),
+            bearings: (0..distances.len())
+                .map(|i| (i as f64 - middle) * 20.0)
+                .collect(),
+            distances: distances.to_vec(),
+            timestamp: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn widest_gap_is_the_longest_clear_run() {
+        let scan = scan(&[50.0, 10.0, 10.0, 60.0, 70.0, 80.0, 90.0]);
+
+        assert_eq!(scan.widest_gap(20.0), Some(30.0));
+    }
+
+    #[test]
+    fn widest_gap_reaches_the_ends_of_the_sweep() {
+        let scan = scan(&[10.0, 10.0, 10.0, 50.0, 50.0]);
+
+        assert_eq!(scan.widest_gap(20.0), Some(30.0));
+    }
+
+    #[test]
+    fn widest_gap_ties_go_nearest_straight_ahead() {
+        let scan = scan(&[50.0, 10.0, 10.0, 10.0, 50.0, 50.0, 10.0, 50.0, 50.0]);
+
+        assert_eq!(scan.widest_gap(20.0), Some(10.0));
+    }
+
+    #[test]
+    fn widest_gap_counts_readings_at_the_threshold_as_clear() {
+        assert_eq!(scan(&[10.0, 20.0, 10.0]).widest_gap(20.0), Some(0.0));
+        assert_eq!(scan(&[10.0, 19.9, 10.0]).widest_gap(20.0), None);
+        assert_eq!(scan(&[]).widest_gap(20.0), None);
+    }
+}
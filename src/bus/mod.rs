@@ -1,2 +1,3 @@
 pub mod event;
 pub mod event_bus;
+pub mod state;
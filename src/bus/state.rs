@@ -0,0 +1,45 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::bus::event::{
+    BehaviourTransition, Ldr, Led, Mode, MotorCommand, RangeScan, Recording, RobotPose,
+    ServoCommand, Ultrasound, WheelCommand, WheelSpeed,
+};
+
+/// Latest known value of everything published on the bus, kept so that
+/// request/response consumers (e.g. the web UI) don't have to subscribe.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RobotState {
+    pub mode: Mode,
+    /// Latest state reported by the running behaviour
+    pub behaviour: Option<BehaviourTransition>,
+    pub motor: Option<MotorCommand>,
+    /// Set instead of `motor` while a behaviour steers each side separately
+    pub wheels: Option<WheelCommand>,
+    /// Measured by the wheel encoders, when fitted
+    pub wheel_speed: Option<WheelSpeed>,
+    pub servo: Option<ServoCommand>,
+    pub ultrasound: Option<Ultrasound>,
+    /// Most recent sweep of the ultrasound across the servo's range
+    pub scan: Option<RangeScan>,
+    /// Dead-reckoned pose from the odometry node
+    pub pose: Option<RobotPose>,
+    pub ldr: Option<Ldr>,
+    pub led: Option<Led>,
+    pub recording: Option<Recording>,
+    /// Last time each node was heard from on the bus
+    pub nodes: BTreeMap<&'static str, DateTime<Utc>>,
+}
+
+impl RobotState {
+    pub fn seen(&mut self, node: &'static str) {
+        self.nodes.insert(node, Utc::now());
+    }
+}
+
+pub type SharedState = Arc<RwLock<RobotState>>;
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    config::Config,
+    hal::playback::image_paths,
+    vision::calibration::{self, Chessboard},
+};
+
+const USAGE: &str =
+    "Usage: hello_robot calibrate [IMAGE_DIR] [--board COLSxROWS] [--square SIZE] [--output FILE]
+
+Finds a chessboard in each image (the snapshot gallery by default) and writes
+the camera intrinsics to FILE (camera_calibration.yaml by default, .json also
+works). --board counts inner corners, 9x6 by default. --square is the edge
+length of one square in metres, 0.025 by default.";
+
+/// `hello_robot calibrate`: camera calibration from saved chessboard images
+pub fn run(args: &[String], config: &Config) -> Result<()> {
+    let mut dir = config.snapshots.dir.clone();
+    let mut output = PathBuf::from("camera_calibration.yaml");
+    let mut board = Chessboard {
+        columns: 9,
+        rows: 6,
+        square_size: 0.025,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--board" => {
+                let value = args.next().context("--board needs a value")?;
+                let (columns, rows) = value
+                    .split_once('x')
+                    .with_context(|| format!("--board must be COLSxROWS, got {value}"))?;
+
+                board.columns = columns.parse().context("Invalid board columns")?;
+                board.rows = rows.parse().context("Invalid board rows")?;
+            }
+            "--square" => {
+                let value = args.next().context("--square needs a value")?;
+                board.square_size = value.parse().context("Invalid square size")?;
+            }
+            "--output" => {
+                output = PathBuf::from(args.next().context("--output needs a value")?);
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            flag if flag.starts_with('-') => bail!("Unknown option {flag}\n\n{USAGE}"),
+            path => dir = PathBuf::from(path),
+        }
+    }
+
+    if board.columns < 2 || board.rows < 2 {
+        bail!("Board needs at least 2x2 inner corners");
+    }
+
+    // The camera node undistorts frames once it has a calibration, and
+    // snapshots are taken from those
+    if let Some(path) = &config.camera.calibration
+        && path.is_file()
+    {
+        eprintln!(
+            "Warning: [camera] calibration is set to {}, so snapshots taken with it are already \
+             undistorted. Comment it out and restart before taking calibration snapshots.",
+            path.display()
+        );
+    }
+
+    let images = image_paths(&dir.to_string_lossy())?;
+    println!(
+        "Calibrating from {} images in {} ({}x{} board)",
+        images.len(),
+        dir.display(),
+        board.columns,
+        board.rows
+    );
+
+    let result = calibration::calibrate(&images, board)?;
+
+    for path in &result.rejected {
+        println!(
+            "  skipped {}, no full chessboard or a different image size",
+            path.display()
+        );
+    }
+
+    result
+        .calibration
+        .save(&output, result.rms_error)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+
+    println!(
+        "Used {} views, reprojection error {:.3} px",
+        result.used.len(),
+        result.rms_error
+    );
+    println!("Wrote {}", output.display());
+
+    if result.rms_error > 1.0 {
+        println!("Error is high, retake blurred or similar views and try again");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::{
+    behaviour::{
+        line_follow::LineFollowConfig, mission::MissionConfig, script::ScriptConfig,
+        sentry::SentryConfig, wall_follow::WallFollowConfig, wander::WanderConfig,
+    },
+    hal::{camera::CameraSettings, recorder::RecordingConfig},
+    mapping::MapConfig,
+    nodes::{
+        encoder::EncoderConfig, motor::MotorConfig, odometry::OdometryConfig,
+        scanner::ScannerConfig, servo::ServoConfig,
+    },
+    vision::VisionConfig,
+};
+
+// Path to the config file. Falls back to `DEFAULT_CONFIG_PATH` when unset.
+const CONFIG_PATH_ENV: &str = "HELLO_ROBOT_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "hello_robot.toml";
+
+/// Robot configuration, loaded once at startup. Every section is optional
+/// and falls back to the defaults the robot was built with.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub camera: CameraSettings,
+    pub recording: RecordingConfig,
+    pub snapshots: SnapshotConfig,
+    pub vision: VisionConfig,
+    pub motor: MotorConfig,
+    pub encoders: EncoderConfig,
+    pub servo: ServoConfig,
+    pub scanner: ScannerConfig,
+    pub odometry: OdometryConfig,
+    pub map: MapConfig,
+    pub sentry: SentryConfig,
+    pub wander: WanderConfig,
+    pub line_follow: LineFollowConfig,
+    pub wall_follow: WallFollowConfig,
+    pub mission: MissionConfig,
+    pub script: ScriptConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    /// Gallery directory holding each snapshot's JPEG and JSON sidecar
+    pub dir: PathBuf,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        SnapshotConfig {
+            dir: PathBuf::from("snapshots"),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self> {
+        let config = Self::read()?;
+
+        config
+            .camera
+            .validate()
+            .context("Invalid [camera] config")?;
+        config.map.validate().context("Invalid [map] config")?;
+
+        // Without speed measurements the controllers never run
+        if config.motor.pid.enabled && !config.encoders.enabled {
+            bail!("Invalid [motor.pid] config: needs [encoders] enabled");
+        }
+
+        Ok(config)
+    }
+
+    /// Reads the config like `load`, without validating it. For `calibrate`,
+    /// which writes the `[camera] calibration` file validation expects.
+    pub fn read() -> Result<Self> {
+        let (path, required) = match std::env::var(CONFIG_PATH_ENV) {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_PATH.to_string(), false),
+        };
+
+        if !required && !Path::new(&path).exists() {
+            println!("No config file at {path}, using defaults");
+            return Ok(Config::default());
+        }
+
+        let text =
+            std::fs::read_to_string(&path).with_context(|| format!("Failed to read {path}"))?;
+        let config: Config =
+            toml::from_str(&text).with_context(|| format!("Failed to parse {path}"))?;
+
+        println!("Loaded config from {path}");
+
+        Ok(config)
+    }
+}
@@ -1,19 +1,302 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
 
 use anyhow::{Context, Ok, Result};
-use opencv::{core::Vector, imgcodecs, prelude::*, videoio};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use opencv::{
+    core::{self, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+    videoio,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::hal::playback::Playback;
+
+/// Where frames are captured from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum CameraSource {
+    /// Raspberry Pi camera via libcamera's GStreamer element
+    Libcamera,
+    /// V4L2 device, either a path such as `/dev/video0` or an index
+    V4l2 { device: String },
+    /// Video file or network stream URL, opened with whichever backend accepts it
+    Uri { uri: String },
+    /// Complete GStreamer pipeline ending in an appsink producing BGR
+    Gstreamer { pipeline: String },
+    /// Recorded video file or directory of images, played back at `rate`
+    /// frames per second (the video's own rate if unset)
+    Playback {
+        path: String,
+        rate: Option<f64>,
+        #[serde(rename = "loop", default)]
+        looping: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub source: CameraSource,
+    pub width: i32,
+    pub height: i32,
+    /// Target capture rate, frames per second
+    pub fps: u32,
+    /// JPEG encoder quality, 1..=100
+    pub jpeg_quality: i32,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    /// Clockwise rotation in degrees: 0, 90, 180 or 270
+    pub rotate: u16,
+    /// Intrinsics file written by `hello_robot calibrate`. When set, frames
+    /// are published undistorted.
+    pub calibration: Option<PathBuf>,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            source: CameraSource::Libcamera,
+            width: 640,
+            height: 480,
+            fps: 10,
+            jpeg_quality: 80,
+            flip_horizontal: false,
+            flip_vertical: false,
+            rotate: 0,
+            calibration: None,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub fn validate(&self) -> Result<()> {
+        if self.width <= 0 || self.height <= 0 {
+            anyhow::bail!(
+                "Resolution must be positive, got {}x{}",
+                self.width,
+                self.height
+            );
+        }
+
+        if !(1..=60).contains(&self.fps) {
+            anyhow::bail!("fps must be between 1 and 60, got {}", self.fps);
+        }
+
+        if !(1..=100).contains(&self.jpeg_quality) {
+            anyhow::bail!(
+                "jpeg_quality must be between 1 and 100, got {}",
+                self.jpeg_quality
+            );
+        }
+
+        if ![0, 90, 180, 270].contains(&self.rotate) {
+            anyhow::bail!("rotate must be 0, 90, 180 or 270, got {}", self.rotate);
+        }
+
+        if let Some(path) = &self.calibration
+            && !path.is_file()
+        {
+            anyhow::bail!("Calibration file {} not found", path.display());
+        }
+
+        if let CameraSource::Playback {
+            rate: Some(rate), ..
+        } = self.source
+            && !(rate > 0.0 && rate <= 60.0)
+        {
+            anyhow::bail!("Playback rate must be between 0 and 60, got {rate}");
+        }
+
+        Ok(())
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.fps.max(1) as f64)
+    }
+}
+
+/// A single camera frame, shared between all consumers
+#[derive(Debug)]
+pub struct Frame {
+    /// Increments by one for every captured frame
+    pub seq: u64,
+    pub captured_at: DateTime<Utc>,
+    pub width: i32,
+    pub height: i32,
+    /// Decoded frame as captured, for vision processing
+    pub mat: Mat,
+    /// Encoded frame for streaming, exactly as captured
+    pub jpeg: Bytes,
+    /// JPEG quality `jpeg` was encoded at
+    pub quality: i32,
+    /// The frame with the HUD and detector boxes drawn on, and its encoding.
+    /// Only rendered while a stream is showing the overlay.
+    pub overlay: Option<(Mat, Bytes)>,
+    // Smaller or lower quality encodings asked for by streams, made once per
+    // frame and shared by every client asking for the same one
+    variants: Mutex<HashMap<Variant, Arc<Encoding>>>,
+}
+
+// A variant's encoding, or why it failed, filled in by whichever client asks first
+type Encoding = OnceLock<Result<Bytes, String>>;
+
+/// How a stream wants its frames encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Variant {
+    pub overlay: bool,
+    /// Scaled down to this width, keeping the aspect ratio
+    pub width: Option<i32>,
+    pub quality: Option<i32>,
+}
+
+impl Frame {
+    /// The frame encoded as `variant` asks, falling back to the raw frame when
+    /// no overlay has been rendered
+    pub fn encoded(&self, variant: Variant) -> Result<Bytes> {
+        let overlay = self.overlay.as_ref().filter(|_| variant.overlay);
+
+        // Normalise so that equivalent requests share a cache entry
+        let variant = Variant {
+            overlay: overlay.is_some(),
+            width: variant.width.filter(|width| *width < self.width),
+            quality: variant.quality.filter(|quality| *quality != self.quality),
+        };
+
+        if variant.width.is_none() && variant.quality.is_none() {
+            return Ok(overlay.map_or(&self.jpeg, |(_, jpeg)| jpeg).clone());
+        }
+
+        let cell = self
+            .variants
+            .lock()
+            .unwrap()
+            .entry(variant)
+            .or_default()
+            .clone();
+
+        let encoded = cell.get_or_init(|| {
+            let mat = overlay.map_or(&self.mat, |(mat, _)| mat);
+
+            encode_variant(mat, variant.width, variant.quality.unwrap_or(self.quality))
+                .map(Bytes::from)
+                .map_err(|e| format!("{e:#}"))
+        });
+
+        encoded.clone().map_err(anyhow::Error::msg)
+    }
+}
+
+fn encode_variant(mat: &Mat, width: Option<i32>, quality: i32) -> Result<Vec<u8>> {
+    let Some(width) = width else {
+        return encode_jpeg(mat, quality);
+    };
+
+    let height = (mat.rows() as f64 * width as f64 / mat.cols() as f64).round() as i32;
+    let mut resized = Mat::default();
+    imgproc::resize(
+        mat,
+        &mut resized,
+        Size::new(width, height.max(1)),
+        0.0,
+        0.0,
+        imgproc::INTER_AREA,
+    )?;
+
+    encode_jpeg(&resized, quality)
+}
+
+pub type FrameRx = watch::Receiver<Option<Arc<Frame>>>;
 
 #[derive(Debug, Clone)]
 pub struct CameraState {
-    pub latest_frame: Arc<Mutex<Vec<u8>>>,
+    frames: Arc<watch::Sender<Option<Arc<Frame>>>>,
+    settings: Arc<watch::Sender<CameraSettings>>,
 }
 
 impl CameraState {
-    pub fn new() -> Self {
+    pub fn new(settings: CameraSettings) -> Self {
+        let (frames, _) = watch::channel(None);
+        let (settings, _) = watch::channel(settings);
+
         CameraState {
-            latest_frame: Arc::new(Mutex::new(Vec::<u8>::new())),
+            frames: Arc::new(frames),
+            settings: Arc::new(settings),
         }
     }
+
+    /// Publishes a newly captured frame, waking every subscriber
+    pub fn publish(&self, mat: Mat, jpeg: Vec<u8>, quality: i32, overlay: Option<(Mat, Vec<u8>)>) {
+        let seq = self
+            .frames
+            .borrow()
+            .as_ref()
+            .map_or(0, |frame| frame.seq + 1);
+
+        self.frames.send_replace(Some(Arc::new(Frame {
+            seq,
+            captured_at: Utc::now(),
+            width: mat.cols(),
+            height: mat.rows(),
+            mat,
+            jpeg: Bytes::from(jpeg),
+            quality,
+            overlay: overlay.map(|(mat, jpeg)| (mat, Bytes::from(jpeg))),
+            variants: Mutex::default(),
+        })));
+    }
+
+    pub fn latest(&self) -> Option<Arc<Frame>> {
+        self.frames.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> FrameRx {
+        self.frames.subscribe()
+    }
+
+    pub fn settings(&self) -> CameraSettings {
+        self.settings.borrow().clone()
+    }
+
+    /// Replaces the capture settings. The camera node reopens the capture
+    /// when it notices the change.
+    pub fn update_settings(&self, settings: CameraSettings) {
+        self.settings.send_replace(settings);
+    }
+
+    pub fn watch_settings(&self) -> watch::Receiver<CameraSettings> {
+        self.settings.subscribe()
+    }
+}
+
+/// Anything the camera node can pull frames from
+pub trait FrameSource {
+    /// Next frame, or `None` once a finite source has run out
+    fn next_frame(&mut self) -> Result<Option<Mat>>;
+
+    /// Pace the source wants to be read at, if not the configured fps
+    fn frame_interval(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Opens the source described by `settings`
+pub fn open_source(settings: &CameraSettings) -> Result<Box<dyn FrameSource>> {
+    match &settings.source {
+        CameraSource::Playback {
+            path,
+            rate,
+            looping,
+        } => Ok(Box::new(Playback::open(path, *rate, *looping)?)),
+        _ => Ok(Box::new(Camera::open(settings)?)),
+    }
 }
 
 pub struct Camera {
@@ -21,11 +304,40 @@ pub struct Camera {
 }
 
 impl Camera {
-    pub fn new() -> Result<Self> {
-        let pipeline =
-            "libcamerasrc ! video/x-raw,format=BGR,width=640,height=480 ! videoconvert ! appsink";
-        let cap = videoio::VideoCapture::from_file(pipeline, videoio::CAP_GSTREAMER)
-            .context("Failed to open GStreamer pipeline")?;
+    pub fn open(settings: &CameraSettings) -> Result<Self> {
+        let cap = match &settings.source {
+            CameraSource::Libcamera => {
+                let pipeline = format!(
+                    "libcamerasrc ! video/x-raw,width={},height={},framerate={}/1 ! videoconvert ! video/x-raw,format=BGR ! appsink drop=true max-buffers=1",
+                    settings.width, settings.height, settings.fps
+                );
+
+                videoio::VideoCapture::from_file(&pipeline, videoio::CAP_GSTREAMER)
+                    .context("Failed to open libcamera pipeline")?
+            }
+            CameraSource::V4l2 { device } => {
+                let mut cap = match device.parse::<i32>().ok() {
+                    Some(index) => videoio::VideoCapture::new(index, videoio::CAP_V4L2),
+                    None => videoio::VideoCapture::from_file(device, videoio::CAP_V4L2),
+                }
+                .with_context(|| format!("Failed to open V4L2 device {device}"))?;
+
+                cap.set(videoio::CAP_PROP_FRAME_WIDTH, settings.width as f64)?;
+                cap.set(videoio::CAP_PROP_FRAME_HEIGHT, settings.height as f64)?;
+                cap.set(videoio::CAP_PROP_FPS, settings.fps as f64)?;
+
+                cap
+            }
+            CameraSource::Uri { uri } => videoio::VideoCapture::from_file(uri, videoio::CAP_ANY)
+                .with_context(|| format!("Failed to open {uri}"))?,
+            CameraSource::Gstreamer { pipeline } => {
+                videoio::VideoCapture::from_file(pipeline, videoio::CAP_GSTREAMER)
+                    .context("Failed to open GStreamer pipeline")?
+            }
+            CameraSource::Playback { .. } => {
+                anyhow::bail!("Playback sources are opened with Playback::open")
+            }
+        };
 
         if !cap.is_opened()? {
             anyhow::bail!("Camera was not opened");
@@ -44,14 +356,61 @@ impl Camera {
 
         Ok(frame)
     }
+}
 
-    pub fn frame_jpeg(&mut self) -> Result<Vec<u8>> {
-        let mat = self.frame_mat()?;
+impl FrameSource for Camera {
+    fn next_frame(&mut self) -> Result<Option<Mat>> {
+        self.frame_mat().map(Some)
+    }
+}
 
-        let mut buf = Vector::<u8>::new();
+/// Flip and rotation applied to every captured frame
+pub struct Orientation {
+    flip: Option<i32>,
+    rotate: Option<i32>,
+}
 
-        imgcodecs::imencode(".jpg", &mat, &mut buf, &Vector::new())?;
+impl Orientation {
+    pub fn new(settings: &CameraSettings) -> Self {
+        let flip = match (settings.flip_horizontal, settings.flip_vertical) {
+            (false, false) => None,
+            (true, false) => Some(1),
+            (false, true) => Some(0),
+            (true, true) => Some(-1),
+        };
 
-        Ok(buf.to_vec())
+        let rotate = match settings.rotate {
+            90 => Some(core::ROTATE_90_CLOCKWISE),
+            180 => Some(core::ROTATE_180),
+            270 => Some(core::ROTATE_90_COUNTERCLOCKWISE),
+            _ => None,
+        };
+
+        Orientation { flip, rotate }
     }
+
+    pub fn apply(&self, mut frame: Mat) -> Result<Mat> {
+        if let Some(code) = self.flip {
+            let mut flipped = Mat::default();
+            core::flip(&frame, &mut flipped, code)?;
+            frame = flipped;
+        }
+
+        if let Some(code) = self.rotate {
+            let mut rotated = Mat::default();
+            core::rotate(&frame, &mut rotated, code)?;
+            frame = rotated;
+        }
+
+        Ok(frame)
+    }
+}
+
+pub fn encode_jpeg(mat: &Mat, quality: i32) -> Result<Vec<u8>> {
+    let mut buf = Vector::<u8>::new();
+    let params = Vector::from_slice(&[imgcodecs::IMWRITE_JPEG_QUALITY, quality]);
+
+    imgcodecs::imencode(".jpg", mat, &mut buf, &params)?;
+
+    Ok(buf.to_vec())
 }
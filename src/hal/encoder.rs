@@ -0,0 +1,70 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicI8, AtomicI64, Ordering},
+};
+
+use anyhow::{Context, Result};
+use rppal::gpio::{Gpio, InputPin, Trigger};
+
+/// Wheel encoder counting both edges of channel A in an interrupt. With a
+/// second channel it's read as quadrature and knows which way the wheel
+/// turns; with one, ticks take the sign last given to `set_direction`.
+pub struct Encoder {
+    a: InputPin,
+    ticks: Arc<AtomicI64>,
+    direction: Arc<AtomicI8>,
+}
+
+impl Encoder {
+    pub fn new(a: u8, b: Option<u8>) -> Result<Encoder> {
+        let gpio = Gpio::new().context("Failed to initialize GPIO")?;
+        let mut a = gpio.get(a)?.into_input_pullup();
+        let b = match b {
+            Some(pin) => Some(gpio.get(pin)?.into_input_pullup()),
+            None => None,
+        };
+
+        let ticks = Arc::new(AtomicI64::new(0));
+        let direction = Arc::new(AtomicI8::new(1));
+
+        let counter = ticks.clone();
+        let sign = direction.clone();
+        a.set_async_interrupt(Trigger::Both, move |level| {
+            let step = match &b {
+                // B lags A going forwards, so the two differ just after an
+                // edge on A
+                Some(b) if b.read() == level => -1,
+                Some(_) => 1,
+                None => sign.load(Ordering::Relaxed) as i64,
+            };
+            counter.fetch_add(step, Ordering::Relaxed);
+        })
+        .context("Failed to set encoder interrupt")?;
+
+        Ok(Self {
+            a,
+            ticks,
+            direction,
+        })
+    }
+
+    /// Edges counted since start, negative when the wheel has turned back
+    pub fn ticks(&self) -> i64 {
+        self.ticks.load(Ordering::Relaxed)
+    }
+
+    /// Which way a single-channel encoder counts: forward for positive,
+    /// backward for negative. Zero leaves it as it was, since a stopped
+    /// wheel may still be coasting. Quadrature encoders ignore this.
+    pub fn set_direction(&self, direction: i8) {
+        if direction != 0 {
+            self.direction.store(direction.signum(), Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        let _ = self.a.clear_async_interrupt();
+    }
+}
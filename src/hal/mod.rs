@@ -1,6 +1,9 @@
 pub mod camera;
+pub mod encoder;
 pub mod ldr;
 pub mod motor;
 pub mod neopixel;
+pub mod playback;
+pub mod recorder;
 pub mod servo;
 pub mod ultrasound;
@@ -43,6 +43,15 @@ impl Motor {
         Ok(())
     }
 
+    /// Forward for positive speeds, backward for negative, stopped at zero
+    pub fn drive(&mut self, speed: i8 /* -100..100 */) -> Result<()> {
+        match speed {
+            0 => self.stop(),
+            s if s > 0 => self.forward(s as u8),
+            s => self.backward(s.unsigned_abs()),
+        }
+    }
+
     pub fn stop(&mut self) -> Result<()> {
         self.en.set_pwm_frequency(PWM_FREQ, 0.0)?;
 
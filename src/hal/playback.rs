@@ -0,0 +1,130 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use opencv::{imgcodecs, prelude::*, videoio};
+
+use crate::hal::camera::FrameSource;
+
+const IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "bmp"];
+
+/// Plays back recorded footage in place of a live camera: either a video
+/// file (anything OpenCV can decode) or a directory of images in name order
+pub struct Playback {
+    frames: Frames,
+    interval: Option<Duration>,
+    looping: bool,
+}
+
+enum Frames {
+    Video(videoio::VideoCapture),
+    Images { paths: Vec<PathBuf>, next: usize },
+}
+
+impl Playback {
+    pub fn open(path: &str, rate: Option<f64>, looping: bool) -> Result<Self> {
+        let frames = if Path::new(path).is_dir() {
+            Frames::Images {
+                paths: image_paths(path)?,
+                next: 0,
+            }
+        } else {
+            let cap = videoio::VideoCapture::from_file(path, videoio::CAP_ANY)
+                .with_context(|| format!("Failed to open {path}"))?;
+
+            if !cap.is_opened()? {
+                anyhow::bail!("Could not open video {path}");
+            }
+
+            Frames::Video(cap)
+        };
+
+        // Videos default to their own frame rate, image sequences to the camera's
+        let rate = match (&frames, rate) {
+            (_, Some(rate)) => Some(rate),
+            (Frames::Video(cap), None) => {
+                Some(cap.get(videoio::CAP_PROP_FPS)?).filter(|r| *r > 0.0)
+            }
+            (Frames::Images { .. }, None) => None,
+        };
+
+        println!("Playing back {path} (loop: {looping})");
+
+        Ok(Playback {
+            frames,
+            interval: rate.map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            looping,
+        })
+    }
+}
+
+impl FrameSource for Playback {
+    fn next_frame(&mut self) -> Result<Option<Mat>> {
+        match &mut self.frames {
+            Frames::Video(cap) => {
+                let mut frame = Mat::default();
+
+                if !cap.read(&mut frame)? || frame.size()?.width == 0 {
+                    if !self.looping {
+                        return Ok(None);
+                    }
+
+                    cap.set(videoio::CAP_PROP_POS_FRAMES, 0.0)?;
+                    cap.read(&mut frame)?;
+
+                    if frame.size()?.width == 0 {
+                        anyhow::bail!("Video has no frames");
+                    }
+                }
+
+                Ok(Some(frame))
+            }
+            Frames::Images { paths, next } => {
+                if *next >= paths.len() {
+                    if !self.looping {
+                        return Ok(None);
+                    }
+
+                    *next = 0;
+                }
+
+                let path = &paths[*next];
+                *next += 1;
+
+                let frame = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_COLOR)?;
+
+                if frame.size()?.width == 0 {
+                    anyhow::bail!("Could not read image {}", path.display());
+                }
+
+                Ok(Some(frame))
+            }
+        }
+    }
+
+    fn frame_interval(&self) -> Option<Duration> {
+        self.interval
+    }
+}
+
+pub fn image_paths(dir: &str) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {dir}"))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        })
+        .collect();
+
+    if paths.is_empty() {
+        anyhow::bail!("No images found in {dir}");
+    }
+
+    paths.sort();
+
+    Ok(paths)
+}
@@ -0,0 +1,209 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use opencv::{core::Size, prelude::*, videoio};
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingFormat {
+    MjpegAvi,
+    Mp4,
+}
+
+impl RecordingFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::MjpegAvi => "avi",
+            RecordingFormat::Mp4 => "mp4",
+        }
+    }
+
+    fn fourcc(self) -> Result<i32> {
+        let fourcc = match self {
+            RecordingFormat::MjpegAvi => videoio::VideoWriter::fourcc('M', 'J', 'P', 'G')?,
+            RecordingFormat::Mp4 => videoio::VideoWriter::fourcc('m', 'p', '4', 'v')?,
+        };
+
+        Ok(fourcc)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    pub dir: PathBuf,
+    pub format: RecordingFormat,
+    /// Length of each file before a new one is started
+    pub segment_secs: u64,
+    /// Oldest segments are deleted to keep the directory under this size
+    pub max_bytes: u64,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        RecordingConfig {
+            dir: PathBuf::from("recordings"),
+            format: RecordingFormat::MjpegAvi,
+            segment_secs: 300,
+            max_bytes: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+struct Segment {
+    writer: videoio::VideoWriter,
+    path: PathBuf,
+    started: Instant,
+    size: Size,
+}
+
+/// Writes frames to a rolling series of video files
+pub struct Recorder {
+    config: RecordingConfig,
+    active: bool,
+    segment: Option<Segment>,
+}
+
+impl Recorder {
+    pub fn new(config: RecordingConfig) -> Self {
+        Recorder {
+            config,
+            active: false,
+            segment: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.active
+    }
+
+    /// File currently being written, if any
+    pub fn segment_path(&self) -> Option<&Path> {
+        self.segment.as_ref().map(|segment| segment.path.as_path())
+    }
+
+    /// Starts recording. The first segment is opened with the next frame.
+    pub fn start(&mut self) {
+        self.active = true;
+    }
+
+    pub fn stop(&mut self) -> Result<()> {
+        self.active = false;
+        self.close_segment()
+    }
+
+    /// Appends a frame, starting a new segment when the current one is full
+    /// or the frame size has changed. Returns true when a segment was opened.
+    pub fn write(&mut self, frame: &Mat, fps: f64) -> Result<bool> {
+        if !self.active {
+            return Ok(false);
+        }
+
+        let size = frame.size()?;
+        let segment_len = Duration::from_secs(self.config.segment_secs);
+
+        let rotate = self.segment.as_ref().is_some_and(|segment| {
+            segment.started.elapsed() >= segment_len || segment.size != size
+        });
+
+        if rotate {
+            self.close_segment()?;
+        }
+
+        let opened = self.segment.is_none();
+
+        if opened {
+            self.segment = Some(self.open_segment(size, fps)?);
+        }
+
+        if let Some(segment) = self.segment.as_mut() {
+            segment.writer.write(frame)?;
+        }
+
+        Ok(opened)
+    }
+
+    fn open_segment(&self, size: Size, fps: f64) -> Result<Segment> {
+        std::fs::create_dir_all(&self.config.dir)
+            .with_context(|| format!("Failed to create {}", self.config.dir.display()))?;
+
+        self.enforce_quota()?;
+
+        let name = format!(
+            "{}.{}",
+            chrono::Utc::now().format("%Y%m%d-%H%M%S"),
+            self.config.format.extension()
+        );
+        let path = self.config.dir.join(name);
+
+        let writer = videoio::VideoWriter::new(
+            &path.to_string_lossy(),
+            self.config.format.fourcc()?,
+            fps,
+            size,
+            true,
+        )?;
+
+        if !writer.is_opened()? {
+            anyhow::bail!("Could not open {} for writing", path.display());
+        }
+
+        println!("Recording to {}", path.display());
+
+        Ok(Segment {
+            writer,
+            path,
+            started: Instant::now(),
+            size,
+        })
+    }
+
+    fn close_segment(&mut self) -> Result<()> {
+        if let Some(mut segment) = self.segment.take() {
+            segment.writer.release()?;
+            println!("Finished recording {}", segment.path.display());
+        }
+
+        Ok(())
+    }
+
+    // Deletes the oldest recordings until the directory is under quota
+    fn enforce_quota(&self) -> Result<()> {
+        let extension = self.config.format.extension();
+
+        let mut recordings: Vec<(PathBuf, u64)> = std::fs::read_dir(&self.config.dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == extension))
+            .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?.len())))
+            .collect();
+
+        // Segment names are timestamps, so name order is age order
+        recordings.sort();
+
+        let mut total: u64 = recordings.iter().map(|(_, len)| len).sum();
+
+        for (path, len) in recordings {
+            if total <= self.config.max_bytes {
+                break;
+            }
+
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete {}", path.display()))?;
+            total -= len;
+
+            println!("Deleted old recording {}", path.display());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.close_segment();
+    }
+}
@@ -1,6 +1,13 @@
+mod behaviour;
 mod bus;
+mod calibrate;
+mod config;
 mod hal;
+mod mapping;
 mod nodes;
+mod vision;
+
+use std::sync::Arc;
 
 use tokio::{
     sync::{broadcast, watch},
@@ -8,15 +15,22 @@ use tokio::{
 };
 
 use crate::{
-    bus::{event::Event, event_bus::EventBus},
+    bus::{event::Event, event_bus::EventBus, state::SharedState},
+    config::Config,
     hal::camera::CameraState,
+    mapping::{SharedMap, grid::OccupancyGrid},
     nodes::telemetry_bridge::TelemetryTx,
+    vision::overlay::Overlay,
 };
 
 #[derive(Debug, Clone)]
 struct AppState {
+    pub config: Arc<Config>,
     pub bus: EventBus,
     pub camera: CameraState,
+    pub overlay: Overlay,
+    pub state: SharedState,
+    pub map: SharedMap,
     pub shutdown: watch::Receiver<()>,
     pub telemetry_tx: TelemetryTx,
 }
@@ -25,32 +39,74 @@ struct AppState {
 async fn main() {
     println!("Starting Main thread");
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|cmd| cmd == "calibrate") {
+        let config = Config::read().expect("Failed to load config");
+
+        if let Err(e) = calibrate::run(&args[1..], &config) {
+            eprintln!("Calibration failed: {e:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config = Config::load().expect("Failed to load config");
+
     let bus = EventBus::new(64);
     let shutdown_rx = spawn_shutdown_bridge(bus.clone());
     let (telemetry_tx, _) = broadcast::channel(64);
 
     let app_state = AppState {
+        camera: CameraState::new(config.camera.clone()),
+        map: SharedMap::new(OccupancyGrid::new(&config.map).into()),
+        config: Arc::new(config),
         bus,
-        camera: CameraState::new(),
+        overlay: Overlay::default(),
+        state: SharedState::default(),
         shutdown: shutdown_rx,
         telemetry_tx,
     };
 
-    let handles = vec![
-        tokio::spawn(nodes::motor::run(app_state.bus.clone())),
+    let mut handles = vec![
+        tokio::spawn(nodes::motor::run(
+            app_state.bus.clone(),
+            app_state.config.motor.clone(),
+        )),
         tokio::spawn(nodes::ldr::run(app_state.bus.clone())),
         tokio::spawn(nodes::ultrasound::run(app_state.bus.clone())),
         tokio::spawn(nodes::camera::run(app_state.clone())),
+        tokio::spawn(nodes::vision::run(app_state.clone())),
         tokio::spawn(nodes::web::run(app_state.clone())),
         tokio::spawn(nodes::telemetry_bridge::run(app_state.clone())),
+        tokio::spawn(nodes::state::run(app_state.clone())),
         tokio::spawn(nodes::behaviour::run(app_state.clone())),
+        tokio::spawn(nodes::odometry::run(
+            app_state.bus.clone(),
+            app_state.config.odometry.clone(),
+        )),
+        tokio::spawn(nodes::mapping::run(app_state.clone())),
+        tokio::spawn(nodes::scanner::run(
+            app_state.bus.clone(),
+            app_state.config.scanner.clone(),
+            app_state.config.servo.clone(),
+        )),
     ];
 
+    if app_state.config.encoders.enabled {
+        handles.push(tokio::spawn(nodes::encoder::run(
+            app_state.bus.clone(),
+            app_state.config.encoders.clone(),
+        )));
+    }
+
     // Local hardware node
     let local = LocalSet::new();
 
     local.spawn_local(nodes::leds::run(app_state.bus.clone()));
-    local.spawn_local(nodes::servo::run(app_state.bus.clone()));
+    local.spawn_local(nodes::servo::run(
+        app_state.bus.clone(),
+        app_state.config.servo.clone(),
+    ));
 
     local
         .run_until(async {
@@ -0,0 +1,257 @@
+use anyhow::{Result, bail};
+use opencv::{
+    core::{Mat, Vector},
+    imgcodecs,
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{bus::event::RobotPose, mapping::MapConfig};
+
+/// Square grid of cells over the floor, each holding the log-odds that
+/// something is there. 0 is unknown, positive occupied, negative free.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OccupancyGrid {
+    /// Side of each cell, cm
+    pub resolution: f64,
+    /// Cells along each side
+    pub width: usize,
+    pub height: usize,
+    /// Map position of the outer corner of the first cell, cm
+    pub origin: [f64; 2],
+    /// Row by row from `origin`, x increasing along a row and y from one
+    /// row to the next
+    pub cells: Vec<f32>,
+    /// Where the robot was when the last reading was added
+    pub pose: Option<RobotPose>,
+}
+
+impl OccupancyGrid {
+    /// Empty grid centred on the robot's starting point
+    pub fn new(config: &MapConfig) -> Self {
+        let side = (config.size / config.resolution).ceil().max(1.0) as usize;
+        let half = side as f64 * config.resolution / 2.0;
+
+        OccupancyGrid {
+            resolution: config.resolution,
+            width: side,
+            height: side,
+            origin: [-half, -half],
+            cells: vec![0.0; side * side],
+            pose: None,
+        }
+    }
+
+    /// Checks a grid read from disk is consistent with itself
+    pub fn validate(&self) -> Result<()> {
+        if self.resolution <= 0.0 {
+            bail!("resolution must be positive");
+        }
+        if self.cells.len() != self.width * self.height {
+            bail!(
+                "{} cells for a {}x{} grid",
+                self.cells.len(),
+                self.width,
+                self.height
+            );
+        }
+
+        Ok(())
+    }
+
+    // Index of the cell holding map position (x, y), if it's on the map
+    fn cell(&self, x: f64, y: f64) -> Option<usize> {
+        let col = ((x - self.origin[0]) / self.resolution).floor();
+        let row = ((y - self.origin[1]) / self.resolution).floor();
+
+        if col < 0.0 || row < 0.0 || col >= self.width as f64 || row >= self.height as f64 {
+            return None;
+        }
+
+        Some(row as usize * self.width + col as usize)
+    }
+
+    /// Adds one ultrasound reading taken from `pose`, `bearing` degrees
+    /// right of the robot's heading. Cells the echo passed through become
+    /// more likely free, and the one it came back from more likely occupied.
+    pub fn integrate(&mut self, pose: RobotPose, bearing: f64, distance: f64, config: &MapConfig) {
+        self.pose = Some(pose);
+
+        // A failed measurement reads 0
+        if distance <= 0.0 {
+            return;
+        }
+
+        let hit = distance < config.max_range;
+        let range = distance.min(config.max_range);
+        let angle = pose.theta - bearing.to_radians();
+        let (dx, dy) = (angle.cos(), angle.sin());
+
+        // Half-cell steps, so no cell along the ray is skipped
+        let step = self.resolution / 2.0;
+        let end = self.cell(pose.x + range * dx, pose.y + range * dy);
+        let mut last = None;
+
+        for i in 0..(range / step) as usize {
+            let along = i as f64 * step;
+            let cell = self.cell(pose.x + along * dx, pose.y + along * dy);
+
+            if cell != last && cell != end {
+                if let Some(cell) = cell {
+                    self.update(cell, config.miss, config.clamp);
+                }
+                last = cell;
+            }
+        }
+
+        if hit && let Some(end) = end {
+            self.update(end, config.hit, config.clamp);
+        }
+    }
+
+    fn update(&mut self, cell: usize, delta: f32, clamp: f32) {
+        self.cells[cell] = (self.cells[cell] + delta).clamp(-clamp, clamp);
+    }
+
+    /// Greyscale picture of the grid, white free, black occupied and grey
+    /// unknown, with y pointing up the image
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut pixels = Vec::with_capacity(self.cells.len());
+
+        for row in self.cells.chunks(self.width).rev() {
+            pixels.extend(row.iter().map(|&l| {
+                let occupied = 1.0 - 1.0 / (1.0 + l.exp());
+                (255.0 * (1.0 - occupied)).round() as u8
+            }));
+        }
+
+        let mat =
+            Mat::new_rows_cols_with_data::<u8>(self.height as i32, self.width as i32, &pixels)?;
+        let mut buf = Vector::<u8>::new();
+        imgcodecs::imencode(".png", &mat, &mut buf, &Vector::new())?;
+
+        Ok(buf.to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 20x20 cells of 5 cm, centred on the origin
+    fn config() -> MapConfig {
+        MapConfig {
+            size: 100.0,
+            max_range: 40.0,
+            ..MapConfig::default()
+        }
+    }
+
+    fn at(grid: &OccupancyGrid, x: f64, y: f64) -> f32 {
+        grid.cells[grid.cell(x, y).unwrap()]
+    }
+
+    #[test]
+    fn marks_the_ray_free_and_the_echo_occupied() {
+        let config = config();
+        let mut grid = OccupancyGrid::new(&config);
+        assert_eq!((grid.width, grid.height), (20, 20));
+
+        grid.integrate(RobotPose::default(), 0.0, 22.0, &config);
+
+        for x in [1.0, 6.0, 11.0, 16.0] {
+            assert_eq!(at(&grid, x, 1.0), config.miss, "at x = {x}");
+        }
+        assert_eq!(at(&grid, 22.0, 1.0), config.hit);
+        assert_eq!(at(&grid, 27.0, 1.0), 0.0);
+        assert_eq!(at(&grid, 1.0, -4.0), 0.0);
+    }
+
+    #[test]
+    fn bearings_are_right_of_the_heading() {
+        let config = config();
+        let mut grid = OccupancyGrid::new(&config);
+
+        // Facing along y, so right is +x
+        let pose = RobotPose {
+            theta: std::f64::consts::FRAC_PI_2,
+            ..RobotPose::default()
+        };
+        grid.integrate(pose, 90.0, 22.0, &config);
+
+        assert_eq!(at(&grid, 22.0, 1.0), config.hit);
+        assert_eq!(at(&grid, -22.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn out_of_range_readings_only_clear() {
+        let config = config();
+        let mut grid = OccupancyGrid::new(&config);
+
+        grid.integrate(RobotPose::default(), 0.0, 300.0, &config);
+
+        assert_eq!(at(&grid, 36.0, 1.0), config.miss);
+        assert!(grid.cells.iter().all(|&cell| cell <= 0.0));
+    }
+
+    #[test]
+    fn failed_readings_change_nothing() {
+        let config = config();
+        let mut grid = OccupancyGrid::new(&config);
+
+        grid.integrate(RobotPose::default(), 0.0, 0.0, &config);
+
+        assert!(grid.cells.iter().all(|&cell| cell == 0.0));
+        assert!(grid.pose.is_some());
+    }
+
+    #[test]
+    fn log_odds_are_clamped() {
+        let config = config();
+        let mut grid = OccupancyGrid::new(&config);
+
+        for _ in 0..20 {
+            grid.integrate(RobotPose::default(), 0.0, 22.0, &config);
+        }
+
+        assert_eq!(at(&grid, 22.0, 1.0), config.clamp);
+        assert_eq!(at(&grid, 1.0, 1.0), -config.clamp);
+    }
+
+    #[test]
+    fn readings_off_the_map_are_cut_short() {
+        let config = config();
+        let mut grid = OccupancyGrid::new(&config);
+        let pose = RobotPose {
+            x: 45.0,
+            ..RobotPose::default()
+        };
+
+        grid.integrate(pose, 0.0, 30.0, &config);
+
+        assert_eq!(at(&grid, 46.0, 1.0), config.miss);
+        assert!(grid.cells.iter().all(|&cell| cell <= 0.0));
+    }
+
+    #[test]
+    fn config_needs_positive_sizes() {
+        assert!(MapConfig::default().validate().is_ok());
+
+        for config in [
+            MapConfig {
+                resolution: 0.0,
+                ..MapConfig::default()
+            },
+            MapConfig {
+                size: -1.0,
+                ..MapConfig::default()
+            },
+            MapConfig {
+                max_range: f64::NAN,
+                ..MapConfig::default()
+            },
+        ] {
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+}
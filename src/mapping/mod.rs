@@ -0,0 +1,65 @@
+pub mod grid;
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{Result, bail};
+use serde::Deserialize;
+
+use crate::mapping::grid::OccupancyGrid;
+
+pub type SharedMap = Arc<RwLock<OccupancyGrid>>;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MapConfig {
+    /// Side of each grid cell, cm
+    pub resolution: f64,
+    /// Side of the square area mapped, cm, centred on where the robot
+    /// started
+    pub size: f64,
+    /// Readings at or beyond this, cm, are out of the sensor's range. The
+    /// ray up to it is marked free but nothing is marked occupied.
+    pub max_range: f64,
+    /// Log-odds added to the cell a reading ends in
+    pub hit: f32,
+    /// Log-odds added to each cell a reading passes through
+    pub miss: f32,
+    /// Cells' log-odds are kept within ±this, so the map can still change
+    /// its mind about something that moved
+    pub clamp: f32,
+    /// Where maps are saved to and loaded from
+    pub path: PathBuf,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        MapConfig {
+            resolution: 5.0,
+            size: 1000.0,
+            max_range: 200.0,
+            hit: 0.85,
+            miss: -0.4,
+            clamp: 5.0,
+            path: PathBuf::from("map.json"),
+        }
+    }
+}
+
+impl MapConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [
+            ("resolution", self.resolution),
+            ("size", self.size),
+            ("max_range", self.max_range),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                bail!("{name} must be a positive number, got {value}");
+            }
+        }
+
+        Ok(())
+    }
+}
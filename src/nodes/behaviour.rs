@@ -1,93 +1,78 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use rand::seq::IndexedRandom;
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::AppState;
+use crate::behaviour::{self, Behaviour, Context};
 use crate::bus::event::{Event, Mode, MotorCommand, MotorDirection};
 
 pub async fn run(app_state: AppState) {
     let mut bus_rx = app_state.bus.subscribe();
     let bus_tx = app_state.bus.clone();
 
-    let mut mode = Mode::Manual;
-    let mut last_distance = 999.9;
+    let mut active: Option<Box<dyn Behaviour>> = None;
     let mut tick = tokio::time::interval(Duration::from_millis(200));
-    let mut last_intent: Option<MotorDirection> = None;
 
-    loop {
-        tokio::select! {
-            Ok(event)=bus_rx.recv() => {
-                match event {
-                    Event::ModeCommand(new_mode) =>  {
-                        mode = new_mode.mode;
+    // Runs one hook of the active behaviour and publishes what it asked for
+    let call = |active: &mut Option<Box<dyn Behaviour>>,
+                hook: &dyn Fn(&mut dyn Behaviour, &mut Context)| {
+        let Some(behaviour) = active.as_deref_mut() else {
+            return;
+        };
 
-                        match mode {
-                            Mode::Manual => {
-                                // Reset auto intents
-                                last_intent = None;
+        let state = app_state.state.read().unwrap().clone();
+        let mut ctx = Context::new(&state, Instant::now());
+        hook(behaviour, &mut ctx);
 
-                                // Issue all stop
-                                let cmd = MotorCommand {
-                                    direction: MotorDirection::Stop,
-                                    speed: 0,
-                                };
+        for command in ctx.into_commands() {
+            bus_tx.publish(command);
+        }
+    };
 
-                                bus_tx.publish(Event::MotorCommand(cmd));
-                            }
+    loop {
+        tokio::select! {
+            event = bus_rx.recv() => {
+                match event {
+                    Ok(Event::ModeCommand(cmd)) => {
+                        call(&mut active, &|b, ctx| {
+                            b.on_exit(ctx);
+                            println!("[behaviour] {} stopped", b.name());
+                        });
+
+                        // Whatever was running, the wheels stop until the next
+                        // behaviour decides otherwise
+                        if active.take().is_some() || cmd.mode == Mode::Manual {
+                            bus_tx.publish(Event::MotorCommand(MotorCommand {
+                                direction: MotorDirection::Stop,
+                                speed: 0,
+                            }));
+                        }
 
-                            Mode::Automatic => {
-                                // Reset auto intents
-                                last_intent = None;
+                        if let Mode::Automatic(name) = cmd.mode {
+                            active = behaviour::create(name, &app_state.config);
 
-                                println!("[auto] behaviour reset");
+                            if active.is_none() {
+                                eprintln!("Unknown behaviour {name:?}");
                             }
                         }
 
-                        println!("Mode changed to {:?}", mode);
+                        call(&mut active, &|b, ctx| b.on_enter(ctx));
+
+                        println!("Mode changed to {}", cmd.mode);
                     },
-                    Event::Ultrasound(ultrasound) => last_distance = ultrasound.distance,
-                    _ => {}
+                    Ok(Event::Shutdown) | Err(RecvError::Closed) => break,
+                    Ok(event) => call(&mut active, &|b, ctx| b.on_event(&event, ctx)),
+                    // Missed events are gone, the behaviour carries on with
+                    // the next ones
+                    Err(RecvError::Lagged(_)) => {}
                 }
             }
             _ = tick.tick() => {
-                if mode == Mode::Automatic {
-                    let new_intent = if last_distance < 10.0 {
-                        if last_intent.as_ref() == Some(&MotorDirection::Forward) {
-                            Some(random_avoidance_intent())
-                        } else {
-                            last_intent.clone()
-                        }
-                    } else {
-                        Some(MotorDirection::Forward)
-                    };
-
-                    if new_intent.as_ref() != last_intent.as_ref() {
-                        if let Some(intent) = new_intent.as_ref() {
-                            let cmd = MotorCommand {
-                                direction: intent.clone(),
-                                speed: 100,
-                            };
-
-                            bus_tx.publish(Event::MotorCommand(cmd));
-
-                            println!("[AUTO] New intent selected {:?}", intent);
-                        }
-
-                        last_intent = new_intent.clone();
-                    }
-                }
+                call(&mut active, &|b, ctx| b.on_tick(ctx));
             }
         }
     }
-}
-
-fn random_avoidance_intent() -> MotorDirection {
-    let intents = [
-        MotorDirection::Left,
-        MotorDirection::Right,
-        MotorDirection::Backward,
-    ];
-    let mut rng = rand::rng();
 
-    intents.choose(&mut rng).unwrap().clone()
+    call(&mut active, &|b, ctx| b.on_exit(ctx));
+    println!("Behaviour node shutting down");
 }
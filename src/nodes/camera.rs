@@ -1,37 +1,201 @@
-use crate::{AppState, bus::event::Event, hal::camera::Camera};
+use crate::{
+    AppState,
+    bus::{
+        event::{Event, RecordCommand, Recording},
+        event_bus::EventBus,
+    },
+    hal::{
+        camera::{CameraSettings, FrameSource, Orientation, encode_jpeg, open_source},
+        recorder::Recorder,
+    },
+    vision::calibration::{Calibration, Undistorter},
+};
 use std::{
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
+        mpsc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+// How long to wait before retrying a capture that failed to open or read
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
 pub async fn run(app_state: AppState) {
     let mut bus_rx = app_state.bus.subscribe();
 
     let running = Arc::new(AtomicBool::new(true));
     let running_thread = running.clone();
 
+    let (record_tx, record_rx) = mpsc::channel::<RecordCommand>();
+
     let task = tokio::task::spawn_blocking(move || {
-        let mut camera = Camera::new().expect("Could not setup camera");
+        let bus_tx = app_state.bus.clone();
+        let mut recorder = Recorder::new(app_state.config.recording.clone());
+        let mut settings_rx = app_state.camera.watch_settings();
+        let mut settings = settings_rx.borrow_and_update().clone();
+        let mut orientation = Orientation::new(&settings);
+        let mut undistorter = load_undistorter(&settings);
+        let mut source: Option<Box<dyn FrameSource>> = None;
+        let mut finished = false;
 
         while running_thread.load(Ordering::Relaxed) {
-            if let Ok(jpeg) = camera.frame_jpeg() {
-                *app_state.camera.latest_frame.lock().unwrap() = jpeg;
+            while let Ok(cmd) = record_rx.try_recv() {
+                let result = if cmd.recording {
+                    recorder.start();
+                    Ok(())
+                } else {
+                    recorder.stop()
+                };
+
+                if let Err(e) = result {
+                    eprintln!("Recorder error: {e:#}");
+                }
+
+                publish_recording(&bus_tx, &recorder);
+            }
+
+            if settings_rx.has_changed().unwrap_or(false) {
+                settings = settings_rx.borrow_and_update().clone();
+                orientation = Orientation::new(&settings);
+                undistorter = load_undistorter(&settings);
+                source = None;
+                finished = false;
+
+                println!("Camera settings changed, reopening capture");
+            }
+
+            // A non-looping playback has ended, hold the last frame until the settings change
+            if finished {
+                std::thread::sleep(RETRY_DELAY);
+                continue;
+            }
+
+            if source.is_none() {
+                match open_source(&settings) {
+                    Ok(opened) => source = Some(opened),
+                    Err(e) => {
+                        eprintln!("Camera open failed: {e:#}");
+                        std::thread::sleep(RETRY_DELAY);
+                        continue;
+                    }
+                }
+            }
+
+            let Some(src) = source.as_mut() else {
+                continue;
+            };
+
+            let started = Instant::now();
+            let interval = src
+                .frame_interval()
+                .unwrap_or_else(|| settings.frame_interval());
+
+            let frame = src.next_frame().and_then(|frame| {
+                frame
+                    .map(|mat| {
+                        let mat = orientation.apply(mat)?;
+                        match undistorter.as_mut() {
+                            Some(undistorter) => undistorter.apply(mat),
+                            None => Ok(mat),
+                        }
+                    })
+                    .transpose()
+            });
+
+            match frame {
+                Ok(Some(mat)) => {
+                    match recorder.write(&mat, 1.0 / interval.as_secs_f64()) {
+                        Ok(true) => publish_recording(&bus_tx, &recorder),
+                        Ok(false) => {}
+                        Err(e) => {
+                            eprintln!("Recording failed, stopping: {e:#}");
+                            let _ = recorder.stop();
+                            publish_recording(&bus_tx, &recorder);
+                        }
+                    }
+
+                    let overlay = app_state
+                        .overlay
+                        .has_viewers()
+                        .then(|| -> anyhow::Result<_> {
+                            let state = app_state.state.read().unwrap().clone();
+                            let rendered = app_state.overlay.render(&mat, &state)?;
+                            let jpeg = encode_jpeg(&rendered, settings.jpeg_quality)?;
+
+                            Ok((rendered, jpeg))
+                        })
+                        .transpose()
+                        .unwrap_or_else(|e| {
+                            eprintln!("Overlay failed: {e:#}");
+                            None
+                        });
+
+                    match encode_jpeg(&mat, settings.jpeg_quality) {
+                        Ok(jpeg) => {
+                            app_state
+                                .camera
+                                .publish(mat, jpeg, settings.jpeg_quality, overlay)
+                        }
+                        Err(e) => eprintln!("JPEG encode failed: {e:#}"),
+                    }
+                }
+                Ok(None) => {
+                    println!("Playback finished");
+                    finished = true;
+                    continue;
+                }
+                Err(e) => {
+                    eprintln!("Camera read failed, reopening: {e:#}");
+                    source = None;
+                    std::thread::sleep(RETRY_DELAY);
+                    continue;
+                }
             }
 
-            std::thread::sleep(Duration::from_millis(100));
+            std::thread::sleep(interval.saturating_sub(started.elapsed()));
         }
     });
 
-    while let Ok(event) = bus_rx.recv().await {
-        if matches!(event, Event::Shutdown) {
-            println!("Camera node shutting down");
-            break;
+    loop {
+        match bus_rx.recv().await {
+            Ok(Event::RecordCommand(cmd)) => {
+                let _ = record_tx.send(cmd);
+            }
+            Ok(Event::Shutdown) => {
+                println!("Camera node shutting down");
+                break;
+            }
+            Err(_) => break,
+            _ => {}
         }
     }
 
     running.store(false, Ordering::Relaxed);
     let _ = task.await;
 }
+
+fn load_undistorter(settings: &CameraSettings) -> Option<Undistorter> {
+    let path = settings.calibration.as_ref()?;
+
+    match Calibration::load(path) {
+        Ok(calibration) => {
+            println!("Undistorting frames with {}", path.display());
+            Some(Undistorter::new(calibration))
+        }
+        Err(e) => {
+            eprintln!("Publishing distorted frames, calibration failed to load: {e:#}");
+            None
+        }
+    }
+}
+
+fn publish_recording(bus: &EventBus, recorder: &Recorder) {
+    bus.publish(Event::Recording(Recording {
+        recording: recorder.is_recording(),
+        segment: recorder
+            .segment_path()
+            .map(|path| path.display().to_string()),
+    }));
+}
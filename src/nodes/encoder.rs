@@ -0,0 +1,143 @@
+use std::{
+    f64::consts::PI,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    bus::{
+        event::{Event, WheelCommand, WheelSpeed},
+        event_bus::EventBus,
+    },
+    hal::encoder::Encoder,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EncoderConfig {
+    /// Only set once encoders are wired up, the kit doesn't come with any
+    pub enabled: bool,
+    pub left: EncoderPins,
+    pub right: EncoderPins,
+    /// Edges counted per turn of the wheel. Both edges of channel A count,
+    /// so this is twice the slots in the encoder disc.
+    pub ticks_per_rev: f64,
+    pub wheel_diameter_cm: f64,
+    /// Speed measurements published per second
+    pub rate_hz: f64,
+}
+
+/// GPIO pins of one side's encoder
+#[derive(Debug, Clone, Deserialize)]
+pub struct EncoderPins {
+    pub a: u8,
+    /// Second channel of a quadrature encoder, left out for single-channel
+    #[serde(default)]
+    pub b: Option<u8>,
+    /// Set when the encoder counts backwards as the wheel goes forwards
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        EncoderConfig {
+            enabled: false,
+            left: EncoderPins {
+                a: 22,
+                b: Some(23),
+                invert: false,
+            },
+            right: EncoderPins {
+                a: 24,
+                b: Some(25),
+                invert: false,
+            },
+            ticks_per_rev: 40.0,
+            wheel_diameter_cm: 6.5,
+            rate_hz: 20.0,
+        }
+    }
+}
+
+impl EncoderPins {
+    fn signed(&self, value: i64) -> i64 {
+        if self.invert { -value } else { value }
+    }
+}
+
+pub async fn run(bus: EventBus, config: EncoderConfig) {
+    let mut bus_rx = bus.subscribe();
+    let bus_tx = bus.clone();
+
+    // Each side's commanded speed, so single-channel encoders know which
+    // way they're counting
+    let (tx, rx) = mpsc::channel::<WheelCommand>();
+
+    // === Blocking encoder thread ===
+    let task = tokio::task::spawn_blocking(move || {
+        let left = Encoder::new(config.left.a, config.left.b).expect("Left encoder init failed");
+        let right =
+            Encoder::new(config.right.a, config.right.b).expect("Right encoder init failed");
+
+        let cm_per_tick = PI * config.wheel_diameter_cm / config.ticks_per_rev;
+        let period = Duration::from_secs_f64(1.0 / config.rate_hz.max(1.0));
+
+        let mut last = (0, 0, Instant::now());
+        let mut next = Instant::now() + period;
+
+        loop {
+            match rx.recv_timeout(next.saturating_duration_since(Instant::now())) {
+                Ok(wheels) => {
+                    left.set_direction(config.left.signed(wheels.left as i64) as i8);
+                    right.set_direction(config.right.signed(wheels.right as i64) as i8);
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    let now = Instant::now();
+                    let ticks = (
+                        config.left.signed(left.ticks()),
+                        config.right.signed(right.ticks()),
+                    );
+                    let dt = now.duration_since(last.2).as_secs_f64();
+                    let speed = |ticks: i64, last: i64| (ticks - last) as f64 * cm_per_tick / dt;
+
+                    bus_tx.publish(Event::WheelSpeed(WheelSpeed {
+                        left_ticks: ticks.0,
+                        right_ticks: ticks.1,
+                        left: speed(ticks.0, last.0),
+                        right: speed(ticks.1, last.1),
+                    }));
+
+                    last = (ticks.0, ticks.1, now);
+                    next = (next + period).max(now);
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        println!("Encoder Blocking task exited");
+    });
+
+    loop {
+        match bus_rx.recv().await {
+            Ok(Event::MotorCommand(cmd)) => {
+                let _ = tx.send(cmd.wheels());
+            }
+            Ok(Event::WheelCommand(wheels)) => {
+                let _ = tx.send(wheels);
+            }
+            Ok(Event::Shutdown) => {
+                println!("Encoder node shutting down");
+                break;
+            }
+            Err(RecvError::Closed) => break,
+            _ => {}
+        }
+    }
+
+    drop(tx);
+    let _ = task.await;
+}
@@ -1,49 +1,115 @@
 use crate::{
     bus::{
-        event::{Event, Led, Ultrasound},
+        event::{Event, Led, LedCommand, Ultrasound},
         event_bus::EventBus,
     },
     hal::neopixel::Neopixel,
 };
-use tokio::sync::mpsc;
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+// Time each flash stays on, and then off
+const FLASH_PERIOD: Duration = Duration::from_millis(250);
+
+enum LedMsg {
+    Distance(Ultrasound),
+    Flash(LedCommand),
+}
 
 pub async fn run(bus: EventBus) {
     let mut bus_rx = bus.subscribe();
     let bus_tx = bus.clone();
 
-    let (tx, mut rx) = mpsc::channel::<Ultrasound>(16);
+    let (tx, rx) = mpsc::channel::<LedMsg>();
 
     let leds_task = tokio::task::spawn_blocking(move || {
         let mut neopixel = Neopixel::new().expect("Neopixel failed");
         let mut last_distance_i = 0_i32;
+        let mut last_distance = None;
+        let mut flash: Option<(LedCommand, Instant)> = None;
 
-        while let Some(data) = rx.blocking_recv() {
-            let distance_i = (data.distance * 2.0) as i32;
+        let mut shown = None;
 
-            if distance_i != last_distance_i {
-                last_distance_i = distance_i;
+        // Sets and publishes a colour, skipping repeats of what is already showing
+        let mut show = |neopixel: &mut Neopixel, colour: (u8, u8, u8)| {
+            if shown == Some(colour) {
+                return;
+            }
+            shown = Some(colour);
+
+            let (red, green, blue) = colour;
+            let brightness = calculate_brightness(red, green, blue);
 
-                let (red, green, blue) = distance_to_rgb(data.distance);
-                let brightness = calculate_brightness(red, green, blue);
+            if let Err(e) = neopixel.set_pixels(red, green, blue, 0) {
+                eprintln!("Neopixel error: {e}");
+            }
 
-                if let Err(e) = neopixel.set_pixels(red, green, blue, 0) {
-                    eprintln!("Neopixel error: {e}");
+            bus_tx.publish(Event::Led(Led {
+                red,
+                green,
+                blue,
+                brightness: brightness.clamp(0.0, 255.0) as u8,
+            }));
+        };
+
+        loop {
+            let timeout = if flash.is_some() {
+                FLASH_PERIOD
+            } else {
+                Duration::from_secs(1)
+            };
+
+            match rx.recv_timeout(timeout) {
+                Ok(LedMsg::Distance(data)) => {
+                    let distance_i = (data.distance * 2.0) as i32;
+                    last_distance = Some(data.distance);
+
+                    if flash.is_none() && distance_i != last_distance_i {
+                        last_distance_i = distance_i;
+                        show(&mut neopixel, distance_to_rgb(data.distance));
+                    }
                 }
+                Ok(LedMsg::Flash(cmd)) => flash = Some((cmd, Instant::now())),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+
+            let Some((cmd, started)) = &flash else {
+                continue;
+            };
 
-                bus_tx.publish(Event::Led(Led {
-                    red,
-                    green,
-                    blue,
-                    brightness: brightness.clamp(0.0, 255.0) as u8,
-                }));
+            let elapsed = started.elapsed();
+
+            if elapsed >= cmd.duration {
+                // Back to showing distance
+                flash = None;
+                if let Some(distance) = last_distance {
+                    last_distance_i = (distance * 2.0) as i32;
+                    show(&mut neopixel, distance_to_rgb(distance));
+                }
+                continue;
             }
+
+            let on = (elapsed.as_millis() / FLASH_PERIOD.as_millis()).is_multiple_of(2);
+            let colour = if on {
+                (cmd.red, cmd.green, cmd.blue)
+            } else {
+                (0, 0, 0)
+            };
+
+            show(&mut neopixel, colour);
         }
     });
 
     loop {
         match bus_rx.recv().await {
             Ok(Event::Ultrasound(cmd)) => {
-                let _ = tx.send(cmd).await;
+                let _ = tx.send(LedMsg::Distance(cmd));
+            }
+            Ok(Event::LedCommand(cmd)) => {
+                let _ = tx.send(LedMsg::Flash(cmd));
             }
             Ok(Event::Shutdown) => {
                 println!("LEDs node shutting down");
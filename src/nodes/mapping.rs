@@ -0,0 +1,31 @@
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    AppState,
+    bus::event::{Event, RobotPose},
+};
+
+/// Adds each range scan to the shared occupancy grid, at the latest pose
+/// from the odometry node
+pub async fn run(app_state: AppState) {
+    let config = &app_state.config.map;
+    let mut bus_rx = app_state.bus.subscribe();
+    let mut pose = RobotPose::default();
+
+    loop {
+        match bus_rx.recv().await {
+            Ok(Event::Pose(latest)) => pose = latest,
+            Ok(Event::RangeScan(scan)) => {
+                let mut grid = app_state.map.write().unwrap();
+
+                for (&bearing, &distance) in scan.bearings.iter().zip(&scan.distances) {
+                    grid.integrate(pose, bearing, distance, config);
+                }
+            }
+            Ok(Event::Shutdown) | Err(RecvError::Closed) => break,
+            _ => {}
+        }
+    }
+
+    println!("Mapping node shutting down");
+}
@@ -1,9 +1,15 @@
 pub mod behaviour;
 pub mod camera;
+pub mod encoder;
 pub mod ldr;
 pub mod leds;
+pub mod mapping;
 pub mod motor;
+pub mod odometry;
+pub mod scanner;
 pub mod servo;
+pub mod state;
 pub mod telemetry_bridge;
 pub mod ultrasound;
+pub mod vision;
 pub mod web;
@@ -1,23 +1,177 @@
-use std::sync::mpsc;
+use std::{sync::mpsc, time::Instant};
+
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::{
     bus::{
-        event::{Event, MotorCommand, MotorDirection},
+        event::{Event, MotorCommand, MotorDirection, WheelCommand, WheelSpeed},
         event_bus::EventBus,
     },
     hal::motor::Motor,
 };
 
-pub async fn run(bus: EventBus) {
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct MotorConfig {
+    pub pid: PidConfig,
+}
+
+/// Closed-loop speed control on the wheel encoders. When enabled, command
+/// speeds are a fraction of `max_speed_cm_s` rather than of full power.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PidConfig {
+    /// Needs `[encoders]` enabled as well
+    pub enabled: bool,
+    /// Target speed for a command speed of 100, cm/s. Should be a little
+    /// below what the slower side manages at full power.
+    pub max_speed_cm_s: f64,
+    /// Power, in %, per cm/s off target
+    pub kp: f64,
+    /// Power per cm/s off target for each second it stays off
+    pub ki: f64,
+    /// Power per cm/s² of change in the error
+    pub kd: f64,
+}
+
+impl Default for PidConfig {
+    fn default() -> Self {
+        PidConfig {
+            enabled: false,
+            max_speed_cm_s: 40.0,
+            kp: 1.0,
+            ki: 2.0,
+            kd: 0.0,
+        }
+    }
+}
+
+/// One wheel's speed controller. The target's share of full speed is fed
+/// straight through as power, so the PID terms only have to make up for
+/// the wheel running faster or slower than that would suggest.
+#[derive(Debug, Default)]
+struct Pid {
+    integral: f64,
+    last_error: Option<f64>,
+}
+
+impl Pid {
+    // Power for the wheel, -100..=100
+    fn update(&mut self, config: &PidConfig, target: f64, measured: f64, dt: f64) -> i8 {
+        let error = target - measured;
+        let derivative = match self.last_error {
+            Some(last) if dt > 0.0 => (error - last) / dt,
+            _ => 0.0,
+        };
+        self.last_error = Some(error);
+
+        let feedforward = target / config.max_speed_cm_s * 100.0;
+        let output = |integral: f64| {
+            feedforward + config.kp * error + config.ki * integral + config.kd * derivative
+        };
+
+        // Stop integrating while the output is pinned at full power, so the
+        // wheel doesn't overshoot once it catches up
+        let integral = self.integral + error * dt;
+        if output(integral).abs() < 100.0 {
+            self.integral = integral;
+        }
+
+        output(self.integral).clamp(-100.0, 100.0).round() as i8
+    }
+
+    // Takes on a new target. The integral holds what the wheel needs on top
+    // of the feedforward, which still applies going the same way, so it's
+    // only dropped when the wheel stops or reverses. Returns whether it was.
+    fn retarget(&mut self, from: f64, to: f64) -> bool {
+        if from == to {
+            return false;
+        }
+
+        if to == 0.0 || from * to <= 0.0 {
+            *self = Pid::default();
+            return true;
+        }
+
+        // The error jumps with the target, which isn't a change in speed
+        self.last_error = None;
+        false
+    }
+}
+
+/// Both wheels' targets and controllers
+#[derive(Debug, Default)]
+struct SpeedControl {
+    /// cm/s
+    target: (f64, f64),
+    pids: (Pid, Pid),
+    last_measured: Option<Instant>,
+}
+
+impl SpeedControl {
+    // New targets for a command. A wheel starting, stopping or reversing
+    // gets the feedforward straight away, which is the command itself;
+    // otherwise the controllers move to the new targets at the next speed
+    // measurement, keeping what they've learnt.
+    fn set(&mut self, wheels: WheelCommand, config: &PidConfig) -> Option<WheelCommand> {
+        let speed = |command: i8| command as f64 / 100.0 * config.max_speed_cm_s;
+        let target = (speed(wheels.left), speed(wheels.right));
+
+        let left = self.pids.0.retarget(self.target.0, target.0);
+        let right = self.pids.1.retarget(self.target.1, target.1);
+        self.target = target;
+
+        (left || right).then_some(wheels)
+    }
+
+    // Power for each wheel given their measured speeds, if they're moving
+    fn update(&mut self, measured: &WheelSpeed, config: &PidConfig) -> Option<WheelCommand> {
+        let now = Instant::now();
+        let dt = self
+            .last_measured
+            .map_or(0.0, |last| now.duration_since(last).as_secs_f64());
+        self.last_measured = Some(now);
+
+        // Stopped means stopped, not held in place by the controllers
+        if self.target == (0.0, 0.0) {
+            return None;
+        }
+
+        Some(WheelCommand {
+            left: self.pids.0.update(config, self.target.0, measured.left, dt),
+            right: self
+                .pids
+                .1
+                .update(config, self.target.1, measured.right, dt),
+        })
+    }
+}
+
+enum Drive {
+    Motor(MotorCommand),
+    Wheels(WheelCommand),
+}
+
+pub async fn run(bus: EventBus, config: MotorConfig) {
     let mut bus_rx = bus.subscribe();
 
-    let (tx, rx) = mpsc::channel::<MotorCommand>();
+    let (tx, rx) = mpsc::channel::<Drive>();
 
     let motor_task = tokio::task::spawn_blocking(move || {
         let mut left = Motor::new(26, 21, 4).unwrap();
         let mut right = Motor::new(27, 18, 17).unwrap();
 
-        while let Ok(cmd) = rx.recv() {
+        while let Ok(drive) = rx.recv() {
+            let cmd = match drive {
+                Drive::Motor(cmd) => cmd,
+                Drive::Wheels(wheels) => {
+                    let _ = left.drive(wheels.left);
+                    let _ = right.drive(wheels.right);
+                    continue;
+                }
+            };
+
             match cmd.direction {
                 MotorDirection::Forward => {
                     let _ = left.forward(cmd.speed);
@@ -43,16 +197,38 @@ pub async fn run(bus: EventBus) {
         }
     });
 
+    // Under PID control every command goes through the speed controllers
+    let pid = &config.pid;
+    let mut control = SpeedControl::default();
+
     loop {
         match bus_rx.recv().await {
+            Ok(Event::MotorCommand(cmd)) if pid.enabled => {
+                if let Some(wheels) = control.set(cmd.wheels(), pid) {
+                    let _ = tx.send(Drive::Wheels(wheels));
+                }
+            }
+            Ok(Event::WheelCommand(wheels)) if pid.enabled => {
+                if let Some(wheels) = control.set(wheels, pid) {
+                    let _ = tx.send(Drive::Wheels(wheels));
+                }
+            }
             Ok(Event::MotorCommand(cmd)) => {
-                let _ = tx.send(cmd);
+                let _ = tx.send(Drive::Motor(cmd));
+            }
+            Ok(Event::WheelCommand(wheels)) => {
+                let _ = tx.send(Drive::Wheels(wheels));
+            }
+            Ok(Event::WheelSpeed(measured)) if pid.enabled => {
+                if let Some(wheels) = control.update(&measured, pid) {
+                    let _ = tx.send(Drive::Wheels(wheels));
+                }
             }
             Ok(Event::Shutdown) => {
                 println!("Motor node shutting down");
                 break;
             }
-            Err(_) => break,
+            Err(RecvError::Closed) => break,
             _ => {}
         }
     }
@@ -60,3 +236,90 @@ pub async fn run(bus: EventBus) {
     drop(tx);
     let _ = motor_task.await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: f64 = 0.05;
+
+    #[test]
+    fn feedforward_alone_when_on_target() {
+        let config = PidConfig::default();
+        let mut pid = Pid::default();
+
+        assert_eq!(pid.update(&config, 20.0, 20.0, DT), 50);
+        assert_eq!(pid.update(&config, -40.0, -40.0, DT), -100);
+    }
+
+    #[test]
+    fn integral_makes_up_for_a_weak_wheel() {
+        let config = PidConfig::default();
+        let mut pid = Pid::default();
+
+        // Only manages 0.3 cm/s per % of power, so 20 cm/s takes about 67%
+        // rather than the 50% the feedforward guesses
+        let mut power = 50;
+        for _ in 0..400 {
+            power = pid.update(&config, 20.0, power as f64 * 0.3, DT);
+        }
+
+        assert!((66..=68).contains(&power), "settled at {power}%");
+    }
+
+    #[test]
+    fn stops_integrating_while_pinned_at_full_power() {
+        let config = PidConfig::default();
+        let mut pid = Pid::default();
+
+        // A blocked wheel
+        let mut power = 0;
+        for _ in 0..200 {
+            power = pid.update(&config, 20.0, 0.0, DT);
+        }
+        assert!(power >= 95, "only reached {power}%");
+
+        // The integral only grew as far as full power needed, so once free
+        // the wheel is straight back near its usual power
+        assert!(pid.integral <= 15.0);
+        assert!(pid.update(&config, 20.0, 20.0, DT) <= 80);
+    }
+
+    #[test]
+    fn keeps_what_it_learnt_across_speed_changes() {
+        let config = PidConfig::default();
+        let mut control = SpeedControl::default();
+        let forward = |speed| WheelCommand {
+            left: speed,
+            right: speed,
+        };
+
+        // Starting off gets the command straight away
+        assert_eq!(control.set(forward(50), &config), Some(forward(50)));
+        control.pids.0.integral = 5.0;
+
+        // The same again, or faster the same way, leave it to the PIDs
+        assert_eq!(control.set(forward(50), &config), None);
+        assert_eq!(control.set(forward(60), &config), None);
+        assert_eq!(control.pids.0.integral, 5.0);
+        assert_eq!(control.target, (24.0, 24.0));
+
+        // Reversing one side starts that side over
+        let spin = WheelCommand {
+            left: -60,
+            right: 60,
+        };
+        assert_eq!(control.set(spin, &config), Some(spin));
+        assert_eq!(control.pids.0.integral, 0.0);
+
+        // Stopped is left stopped
+        assert_eq!(control.set(forward(0), &config), Some(forward(0)));
+        let measured = WheelSpeed {
+            left_ticks: 0,
+            right_ticks: 0,
+            left: 1.0,
+            right: 1.0,
+        };
+        assert_eq!(control.update(&measured, &config), None);
+    }
+}
@@ -0,0 +1,368 @@
+use std::{path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+use tokio::{
+    sync::broadcast::error::RecvError,
+    time::{Instant, MissedTickBehavior, interval},
+};
+
+use crate::bus::{
+    event::{Event, OdometryCalibration, RobotPose, WheelCommand, WheelSpeed},
+    event_bus::EventBus,
+};
+
+type Matrix = [[f64; 3]; 3];
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OdometryConfig {
+    /// Pose updates published per second
+    pub rate_hz: f64,
+    /// Coefficients measured by `POST /api/odometry/calibrate`. Used in
+    /// place of `speed_cm_s` and `turn_deg_s` once it exists.
+    pub calibration: PathBuf,
+    /// Forward speed at full speed, cm/s, until calibrated
+    pub speed_cm_s: f64,
+    /// Spin rate at full speed, degrees/s, until calibrated
+    pub turn_deg_s: f64,
+    /// Distance between the wheels, cm, for turning measured by the
+    /// encoders
+    pub track_cm: f64,
+    /// Error in distance driven, as a fraction of the distance
+    pub distance_noise: f64,
+    /// Error in angle turned, as a fraction of the angle
+    pub turn_noise: f64,
+}
+
+impl Default for OdometryConfig {
+    fn default() -> Self {
+        OdometryConfig {
+            rate_hz: 10.0,
+            calibration: PathBuf::from("odometry_calibration.toml"),
+            speed_cm_s: 30.0,
+            turn_deg_s: 180.0,
+            track_cm: 14.0,
+            distance_noise: 0.1,
+            turn_noise: 0.1,
+        }
+    }
+}
+
+impl OdometryConfig {
+    /// Saved calibration, or the configured coefficients if there isn't one
+    pub fn load_calibration(&self) -> OdometryCalibration {
+        let configured = OdometryCalibration {
+            speed_cm_s: self.speed_cm_s,
+            turn_deg_s: self.turn_deg_s,
+        };
+
+        let Ok(text) = std::fs::read_to_string(&self.calibration) else {
+            return configured;
+        };
+
+        match toml::from_str(&text) {
+            Ok(calibration) => calibration,
+            Err(e) => {
+                eprintln!(
+                    "Ignoring odometry calibration {}: {e}",
+                    self.calibration.display()
+                );
+                configured
+            }
+        }
+    }
+}
+
+/// Dead reckoning from the wheel speeds. With encoders these are measured;
+/// without, they're the commanded speeds scaled by the calibration, so the
+/// pose is only as good as that. Either way it drifts the longer the robot
+/// drives, which the covariance tracks.
+struct Odometry {
+    pose: RobotPose,
+    calibration: OdometryCalibration,
+    wheels: WheelCommand,
+    // Latest encoder measurement, used in place of `wheels` once there is one
+    measured: Option<WheelSpeed>,
+    since: Instant,
+}
+
+impl Odometry {
+    // Moves the pose on by however long the wheels have been running at
+    // their last command or measured speed
+    fn advance(&mut self, now: Instant, config: &OdometryConfig) {
+        let dt = now.duration_since(self.since).as_secs_f64();
+        self.since = now;
+
+        let (distance, turn) = match &self.measured {
+            Some(speed) => (
+                (speed.left + speed.right) / 2.0 * dt,
+                (speed.right - speed.left) / config.track_cm * dt,
+            ),
+            None => {
+                let (left, right) = (
+                    self.wheels.left as f64 / 100.0,
+                    self.wheels.right as f64 / 100.0,
+                );
+                (
+                    (left + right) / 2.0 * self.calibration.speed_cm_s * dt,
+                    (right - left) / 2.0 * self.calibration.turn_deg_s.to_radians() * dt,
+                )
+            }
+        };
+
+        if distance == 0.0 && turn == 0.0 {
+            return;
+        }
+
+        // Heading half way through the move, for less drift on curves
+        let heading = self.pose.theta + turn / 2.0;
+        let (sin, cos) = heading.sin_cos();
+
+        // How the new pose depends on the old one, and on the distance and
+        // turn, for carrying the uncertainty forward
+        let g = [
+            [1.0, 0.0, -distance * sin],
+            [0.0, 1.0, distance * cos],
+            [0.0, 0.0, 1.0],
+        ];
+        let v = [
+            [cos, -distance * sin / 2.0],
+            [sin, distance * cos / 2.0],
+            [0.0, 1.0],
+        ];
+        let noise = [
+            (config.distance_noise * distance).powi(2),
+            (config.turn_noise * turn).powi(2),
+        ];
+
+        let mut covariance = multiply(&multiply(&g, &self.pose.covariance), &transpose(&g));
+        for (i, row) in covariance.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell += v[i][0] * v[j][0] * noise[0] + v[i][1] * v[j][1] * noise[1];
+            }
+        }
+
+        self.pose = RobotPose {
+            x: self.pose.x + distance * cos,
+            y: self.pose.y + distance * sin,
+            theta: wrap(self.pose.theta + turn),
+            covariance,
+        };
+    }
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn transpose(a: &Matrix) -> Matrix {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = a[j][i];
+        }
+    }
+    out
+}
+
+// Angle in -π..=π
+fn wrap(theta: f64) -> f64 {
+    theta.sin().atan2(theta.cos())
+}
+
+/// Tracks the robot's pose from the motor commands, or the encoders when
+/// they're publishing, and publishes it `rate_hz` times a second
+pub async fn run(bus: EventBus, config: OdometryConfig) {
+    let mut bus_rx = bus.subscribe();
+
+    let mut odometry = Odometry {
+        pose: RobotPose::default(),
+        calibration: config.load_calibration(),
+        wheels: WheelCommand { left: 0, right: 0 },
+        measured: None,
+        since: Instant::now(),
+    };
+
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / config.rate_hz.max(0.1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                odometry.advance(Instant::now(), &config);
+                bus.publish(Event::Pose(odometry.pose));
+            }
+            event = bus_rx.recv() => {
+                match event {
+                    Ok(Event::MotorCommand(cmd)) => {
+                        odometry.advance(Instant::now(), &config);
+                        odometry.wheels = cmd.wheels();
+                    }
+                    Ok(Event::WheelCommand(wheels)) => {
+                        odometry.advance(Instant::now(), &config);
+                        odometry.wheels = wheels;
+                    }
+                    Ok(Event::WheelSpeed(speed)) => {
+                        odometry.advance(Instant::now(), &config);
+                        odometry.measured = Some(speed);
+                    }
+                    Ok(Event::OdometryReset) => {
+                        println!("Odometry reset");
+                        odometry.pose = RobotPose::default();
+                        odometry.since = Instant::now();
+                    }
+                    Ok(Event::OdometryCalibration(calibration)) => {
+                        odometry.advance(Instant::now(), &config);
+                        odometry.calibration = calibration;
+                    }
+                    Ok(Event::Shutdown) | Err(RecvError::Closed) => break,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    println!("Odometry node shutting down");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    use super::*;
+
+    fn odometry(left: i8, right: i8, since: Instant) -> Odometry {
+        Odometry {
+            pose: RobotPose::default(),
+            calibration: OdometryCalibration {
+                speed_cm_s: 30.0,
+                turn_deg_s: 180.0,
+            },
+            wheels: WheelCommand { left, right },
+            measured: None,
+            since,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn drives_straight_at_the_calibrated_speed() {
+        let config = OdometryConfig::default();
+        let t0 = Instant::now();
+        let mut odometry = odometry(100, 100, t0);
+
+        odometry.advance(t0 + Duration::from_secs(2), &config);
+        assert!(close(odometry.pose.x, 60.0));
+        assert!(close(odometry.pose.y, 0.0));
+        assert!(close(odometry.pose.theta, 0.0));
+
+        // Half the command, half the speed
+        odometry.wheels = WheelCommand {
+            left: -50,
+            right: -50,
+        };
+        odometry.advance(t0 + Duration::from_secs(4), &config);
+        assert!(close(odometry.pose.x, 30.0));
+    }
+
+    #[test]
+    fn spins_on_the_spot() {
+        let config = OdometryConfig::default();
+        let t0 = Instant::now();
+        let mut odometry = odometry(-100, 100, t0);
+
+        odometry.advance(t0 + Duration::from_millis(500), &config);
+        assert!(close(odometry.pose.theta, FRAC_PI_2));
+        assert!(close(odometry.pose.x, 0.0) && close(odometry.pose.y, 0.0));
+
+        // Past half a turn the heading wraps round to negative
+        odometry.advance(t0 + Duration::from_millis(1500), &config);
+        assert!(close(odometry.pose.theta, -FRAC_PI_2));
+    }
+
+    #[test]
+    fn curves_use_the_heading_half_way_through() {
+        let config = OdometryConfig::default();
+        let t0 = Instant::now();
+        // 15 cm/s forward while turning 45°/s left
+        let mut odometry = odometry(25, 75, t0);
+
+        odometry.advance(t0 + Duration::from_secs(2), &config);
+
+        let heading = PI / 4.0;
+        assert!(close(odometry.pose.theta, PI / 2.0));
+        assert!(close(odometry.pose.x, 30.0 * heading.cos()));
+        assert!(close(odometry.pose.y, 30.0 * heading.sin()));
+    }
+
+    #[test]
+    fn prefers_the_encoders_once_they_measure() {
+        let config = OdometryConfig::default();
+        let t0 = Instant::now();
+        let mut odometry = odometry(100, 100, t0);
+
+        // Slower than commanded, and turning left
+        odometry.measured = Some(WheelSpeed {
+            left_ticks: 0,
+            right_ticks: 0,
+            left: 10.0,
+            right: 10.0 + config.track_cm * FRAC_PI_2,
+        });
+        odometry.advance(t0 + Duration::from_secs(1), &config);
+
+        assert!(close(odometry.pose.theta, FRAC_PI_2));
+        let distance = 10.0 + config.track_cm * FRAC_PI_2 / 2.0;
+        let heading = FRAC_PI_2 / 2.0;
+        assert!(close(odometry.pose.x, distance * heading.cos()));
+        assert!(close(odometry.pose.y, distance * heading.sin()));
+    }
+
+    #[test]
+    fn uncertainty_grows_with_the_motion() {
+        let config = OdometryConfig::default();
+        let t0 = Instant::now();
+        let mut odometry = odometry(0, 0, t0);
+
+        // Standing still adds nothing
+        odometry.advance(t0 + Duration::from_secs(5), &config);
+        assert_eq!(odometry.pose.covariance, [[0.0; 3]; 3]);
+
+        // Driving straight is uncertain along the way only
+        odometry.wheels = WheelCommand {
+            left: 100,
+            right: 100,
+        };
+        odometry.advance(t0 + Duration::from_secs(7), &config);
+        let covariance = odometry.pose.covariance;
+        assert!(close(covariance[0][0], (0.1 * 60.0f64).powi(2)));
+        assert!(close(covariance[1][1], 0.0));
+        assert!(close(covariance[2][2], 0.0));
+
+        // A turn makes the heading uncertain, and with it the sideways
+        // position after driving on
+        odometry.wheels = WheelCommand {
+            left: -100,
+            right: 100,
+        };
+        odometry.advance(t0 + Duration::from_millis(7100), &config);
+        assert!(odometry.pose.covariance[2][2] > 0.0);
+
+        odometry.wheels = WheelCommand {
+            left: 100,
+            right: 100,
+        };
+        odometry.advance(t0 + Duration::from_secs(9), &config);
+        let covariance = odometry.pose.covariance;
+        assert!(covariance[1][1] > 0.0);
+        assert!(close(covariance[0][1], covariance[1][0]));
+    }
+}
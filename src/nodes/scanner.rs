@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use serde::Deserialize;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::{Instant, sleep, timeout},
+};
+
+use crate::{
+    bus::{
+        event::{Event, RangeScan, ServoCommand},
+        event_bus::EventBus,
+    },
+    nodes::servo::{SERVO_CENTRE, ServoAxis, ServoConfig},
+};
+
+// The ultrasound node only publishes when its average moves, so going this
+// long without a reading means the average has settled where it is
+const QUIET_MS: u64 = 500;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ScannerConfig {
+    /// First servo angle of the sweep
+    pub start_angle: u8,
+    /// Last servo angle of the sweep
+    pub end_angle: u8,
+    /// Degrees between readings
+    pub step: u8,
+    /// Time for the servo to reach each angle before readings are trusted
+    pub settle_ms: u64,
+    /// Successive readings closer than this, cm, count as settled
+    pub tolerance: f64,
+    /// Longest to wait for the reading to settle at each angle
+    pub max_wait_ms: u64,
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        ScannerConfig {
+            start_angle: 10,
+            end_angle: 170,
+            step: 10,
+            settle_ms: 250,
+            tolerance: 1.0,
+            max_wait_ms: 2000,
+        }
+    }
+}
+
+impl ScannerConfig {
+    // Angles to stop at, kept inside the servo's limits
+    fn angles(&self, servo: &ServoConfig) -> Vec<u8> {
+        let start = servo.clamp(self.start_angle.min(self.end_angle) as f64);
+        let end = servo.clamp(self.start_angle.max(self.end_angle) as f64);
+        let mut angles: Vec<u8> = (start..=end).step_by(self.step.max(1) as usize).collect();
+
+        if angles.last() != Some(&end) {
+            angles.push(end);
+        }
+        if self.start_angle > self.end_angle {
+            angles.reverse();
+        }
+
+        angles
+    }
+}
+
+/// Sweeps the ultrasound sensor across the pan servo's range on each
+/// `ScanCommand`, publishing the readings as a `RangeScan` and putting the
+/// servo back where it was
+pub async fn run(bus: EventBus, config: ScannerConfig, servo: ServoConfig) {
+    let mut bus_rx = bus.subscribe();
+    let mut distance = 0.0;
+    let mut angle = SERVO_CENTRE;
+
+    loop {
+        match bus_rx.recv().await {
+            Ok(Event::Ultrasound(ultrasound)) => distance = ultrasound.distance,
+            Ok(Event::Servo(cmd)) => angle = cmd.angle,
+            Ok(Event::ScanCommand) if servo.axis != ServoAxis::Pan => {
+                eprintln!("Scan needs the ultrasound on a pan servo, see [servo] axis");
+            }
+            Ok(Event::ScanCommand) => {
+                println!("Scanning");
+
+                let Some(scan) = sweep(&bus, &mut bus_rx, &config, &servo, distance).await else {
+                    break;
+                };
+                distance = scan.distances.last().copied().unwrap_or(distance);
+                bus.publish(Event::ServoCommand(ServoCommand { angle }));
+
+                println!("Scanned {} angles", scan.angles.len());
+                bus.publish(Event::RangeScan(scan));
+            }
+            Ok(Event::Shutdown) | Err(RecvError::Closed) => break,
+            _ => {}
+        }
+    }
+
+    println!("Scanner node shutting down");
+}
+
+// None if the robot shut down part way through
+async fn sweep(
+    bus: &EventBus,
+    bus_rx: &mut broadcast::Receiver<Event>,
+    config: &ScannerConfig,
+    servo: &ServoConfig,
+    mut distance: f64,
+) -> Option<RangeScan> {
+    let angles = config.angles(servo);
+    let sign = if servo.invert { -1.0 } else { 1.0 };
+    let mut distances = Vec::with_capacity(angles.len());
+
+    for &angle in &angles {
+        bus.publish(Event::ServoCommand(ServoCommand { angle }));
+        sleep(Duration::from_millis(config.settle_ms)).await;
+
+        distance = settled(bus_rx, config, distance).await?;
+        distances.push(distance);
+    }
+
+    Some(RangeScan {
+        bearings: angles
+            .iter()
+            .map(|&angle| sign * (angle as f64 - SERVO_CENTRE as f64))
+            .collect(),
+        angles,
+        distances,
+        timestamp: Utc::now(),
+    })
+}
+
+// Waits for the smoothed distance to stop moving. Readings queued while the
+// servo was turning are worked through first, so they don't count.
+async fn settled(
+    bus_rx: &mut broadcast::Receiver<Event>,
+    config: &ScannerConfig,
+    mut distance: f64,
+) -> Option<f64> {
+    let deadline = Instant::now() + Duration::from_millis(config.max_wait_ms);
+
+    while Instant::now() < deadline {
+        match timeout(Duration::from_millis(QUIET_MS), bus_rx.recv()).await {
+            Err(_) => break,
+            Ok(Ok(Event::Ultrasound(ultrasound))) => {
+                let change = (ultrasound.distance - distance).abs();
+                distance = ultrasound.distance;
+
+                if change < config.tolerance {
+                    break;
+                }
+            }
+            Ok(Ok(Event::Shutdown) | Err(RecvError::Closed)) => return None,
+            _ => {}
+        }
+    }
+
+    Some(distance)
+}
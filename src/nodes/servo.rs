@@ -1,13 +1,120 @@
-use std::{sync::mpsc, time::Duration};
+use std::{
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::{
     bus::{
-        event::{Event, ServoCommand},
+        event::{Event, Face, Mode, ServoCommand},
         event_bus::EventBus,
     },
     hal::servo::Servo,
 };
 
+/// Angle assumed before the servo has been commanded
+pub const SERVO_CENTRE: u8 = 90;
+
+/// Which way the servo moves the camera
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServoAxis {
+    /// Higher angles look right
+    Pan,
+    /// Higher angles look up
+    Tilt,
+}
+
+/// How the camera servo is mounted and how far it may turn
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ServoConfig {
+    pub axis: ServoAxis,
+    /// Camera field of view along `axis`, degrees
+    pub fov_degrees: f64,
+    /// Set when the servo is mounted the other way round
+    pub invert: bool,
+    /// Commands outside these angles are clamped to them
+    pub min_angle: u8,
+    pub max_angle: u8,
+    pub tracking: TrackingConfig,
+}
+
+impl Default for ServoConfig {
+    fn default() -> Self {
+        // Pi camera v2 horizontal field of view
+        ServoConfig {
+            axis: ServoAxis::Pan,
+            fov_degrees: 62.2,
+            invert: false,
+            min_angle: 10,
+            max_angle: 170,
+            tracking: TrackingConfig::default(),
+        }
+    }
+}
+
+/// Proportional control keeping the largest face centred
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TrackingConfig {
+    pub enabled: bool,
+    /// Fraction of the error corrected per face detection
+    pub gain: f64,
+    /// Errors smaller than this many degrees are left alone, so the servo
+    /// doesn't hunt around a face that is already centred
+    pub dead_band: f64,
+    /// Tracking only runs in manual mode, and pauses for this long after
+    /// the servo is commanded, e.g. from the dashboard or by a scan
+    pub pause_secs: f64,
+}
+
+impl Default for TrackingConfig {
+    fn default() -> Self {
+        TrackingConfig {
+            enabled: false,
+            gain: 0.4,
+            dead_band: 3.0,
+            pause_secs: 5.0,
+        }
+    }
+}
+
+impl ServoConfig {
+    /// Degrees to turn for the pixel (x, y) to be centred in the frame
+    pub fn correction(&self, x: i32, y: i32, width: i32, height: i32) -> f64 {
+        // How far the point is from the centre of the frame, -0.5..=0.5
+        let offset = match self.axis {
+            ServoAxis::Pan => x as f64 / width.max(1) as f64 - 0.5,
+            ServoAxis::Tilt => 0.5 - y as f64 / height.max(1) as f64,
+        };
+        let sign = if self.invert { -1.0 } else { 1.0 };
+
+        sign * offset * self.fov_degrees
+    }
+
+    pub fn clamp(&self, angle: f64) -> u8 {
+        angle
+            .round()
+            .clamp(self.min_angle as f64, self.max_angle as f64) as u8
+    }
+
+    // Next angle when tracking `face`, if the servo should move at all
+    fn track(&self, face: &Face, current: u8) -> Option<u8> {
+        let error = self.correction(face.cx, face.cy, face.frame_width, face.frame_height);
+
+        if error.abs() < self.tracking.dead_band {
+            return None;
+        }
+
+        let angle = self.clamp(current as f64 + self.tracking.gain * error);
+
+        (angle != current).then_some(angle)
+    }
+}
+
 // Async
 // * Listens to bus_rx
 // * Decides what should happen
@@ -26,7 +133,7 @@ use crate::{
 //  └──────────────▶   │
 //                     │ waits (blocking OK)
 //                     │ controls hardware
-pub async fn run(bus: EventBus) {
+pub async fn run(bus: EventBus, config: ServoConfig) {
     let mut bus_rx = bus.subscribe();
     let bus_tx = bus.clone();
 
@@ -61,16 +168,37 @@ pub async fn run(bus: EventBus) {
     });
 
     // === Async control loop ===
+    let mut angle = SERVO_CENTRE;
+
+    // Behaviours and scans point the servo themselves, so tracking would
+    // fight them
+    let pause = Duration::from_secs_f64(config.tracking.pause_secs.max(0.0));
+    let mut manual = true;
+    let mut commanded: Option<Instant> = None;
+
     loop {
         match bus_rx.recv().await {
             Ok(Event::ServoCommand(cmd)) => {
-                let _ = tx.send(cmd.angle);
+                angle = config.clamp(cmd.angle as f64);
+                commanded = Some(Instant::now());
+                let _ = tx.send(angle);
+            }
+            Ok(Event::ModeCommand(cmd)) => manual = cmd.mode == Mode::Manual,
+            Ok(Event::FaceDetected(face))
+                if config.tracking.enabled
+                    && manual
+                    && commanded.is_none_or(|at| at.elapsed() >= pause) =>
+            {
+                if let Some(next) = config.track(&face, angle) {
+                    angle = next;
+                    let _ = tx.send(angle);
+                }
             }
             Ok(Event::Shutdown) => {
                 println!("Servo node shutting down");
                 break;
             }
-            Err(_) => break, // bus closed
+            Err(RecvError::Closed) => break,
             _ => {}
         }
     }
@@ -0,0 +1,67 @@
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{AppState, bus::event::Event};
+
+pub async fn run(app_state: AppState) {
+    let mut bus_rx = app_state.bus.subscribe();
+
+    loop {
+        let event = match bus_rx.recv().await {
+            Ok(event) => event,
+            // Missed updates are replaced by the next ones soon enough
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+
+        let mut state = app_state.state.write().unwrap();
+
+        match event {
+            Event::MotorCommand(cmd) => {
+                state.motor = Some(cmd);
+                state.wheels = None;
+                state.seen("motor");
+            }
+            Event::WheelCommand(wheels) => {
+                state.wheels = Some(wheels);
+                state.motor = None;
+                state.seen("motor");
+            }
+            Event::ModeCommand(cmd) => {
+                state.mode = cmd.mode;
+                state.behaviour = None;
+            }
+            Event::BehaviourTransition(transition) => state.behaviour = Some(transition),
+            Event::Ultrasound(ultrasound) => {
+                state.ultrasound = Some(ultrasound);
+                state.seen("ultrasound");
+            }
+            Event::WheelSpeed(speed) => {
+                state.wheel_speed = Some(speed);
+                state.seen("encoders");
+            }
+            Event::RangeScan(scan) => state.scan = Some(scan),
+            Event::Pose(pose) => {
+                state.pose = Some(pose);
+                state.seen("odometry");
+            }
+            Event::Ldr(ldr) => {
+                state.ldr = Some(ldr);
+                state.seen("ldr");
+            }
+            Event::Led(led) => {
+                state.led = Some(led);
+                state.seen("leds");
+            }
+            Event::Recording(recording) => state.recording = Some(recording),
+            Event::Servo(servo) => {
+                state.servo = Some(servo);
+                state.seen("servo");
+            }
+            Event::Shutdown => {
+                println!("State node shutting down");
+                break;
+            }
+            _ => {}
+        }
+    }
+}
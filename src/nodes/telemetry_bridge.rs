@@ -3,7 +3,10 @@ use tokio::sync::broadcast;
 
 use crate::{
     AppState,
-    bus::event::{Event, Ldr, Led, ServoCommand, Ultrasound},
+    bus::event::{
+        BehaviourTransition, Blob, Event, Face, Fiducial, Ldr, Led, Motion, QrCode, RangeScan,
+        RobotPose, ServoCommand, Ultrasound, WheelSpeed,
+    },
 };
 
 #[derive(Serialize, Clone)]
@@ -12,6 +15,15 @@ pub enum Telemetry {
     Ldr(Ldr),
     Led(Led),
     Servo(ServoCommand),
+    Blob(Blob),
+    Fiducial(Fiducial),
+    Motion(Motion),
+    QrCode(QrCode),
+    Face(Face),
+    Behaviour(BehaviourTransition),
+    Scan(RangeScan),
+    Pose(RobotPose),
+    WheelSpeed(WheelSpeed),
 }
 
 pub type TelemetryTx = broadcast::Sender<Telemetry>;
@@ -35,6 +47,35 @@ pub async fn run(app_state: AppState) {
             Event::Servo(servo) => {
                 let _ = app_state.telemetry_tx.send(Telemetry::Servo(servo));
             }
+            Event::BlobDetected(blob) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::Blob(blob));
+            }
+            Event::FiducialDetected(fiducial) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::Fiducial(fiducial));
+            }
+            Event::MotionDetected(motion) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::Motion(motion));
+            }
+            Event::QrCodeDetected(qr) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::QrCode(qr));
+            }
+            Event::FaceDetected(face) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::Face(face));
+            }
+            Event::BehaviourTransition(transition) => {
+                let _ = app_state
+                    .telemetry_tx
+                    .send(Telemetry::Behaviour(transition));
+            }
+            Event::RangeScan(scan) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::Scan(scan));
+            }
+            Event::WheelSpeed(speed) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::WheelSpeed(speed));
+            }
+            Event::Pose(pose) => {
+                let _ = app_state.telemetry_tx.send(Telemetry::Pose(pose));
+            }
             Event::Shutdown => {
                 println!("Telemetry node shutting down");
                 break;
@@ -0,0 +1,77 @@
+use crate::{AppState, bus::event::Event, vision};
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
+// How often the blocking thread checks whether it should stop while no frames arrive
+const FRAME_WAIT: Duration = Duration::from_millis(200);
+
+pub async fn run(app_state: AppState) {
+    let mut bus_rx = app_state.bus.subscribe();
+
+    let mut detectors = match vision::detectors(&app_state.config) {
+        Ok(detectors) => detectors,
+        Err(e) => {
+            eprintln!("Vision node disabled, detector setup failed: {e:#}");
+            return;
+        }
+    };
+
+    if detectors.is_empty() {
+        println!("Vision node idle, no detectors enabled");
+        return;
+    }
+
+    let running = Arc::new(AtomicBool::new(true));
+    let running_thread = running.clone();
+    let runtime = tokio::runtime::Handle::current();
+
+    // === Blocking vision thread ===
+    // Frames that arrive while a detector is still busy are skipped
+    let task = tokio::task::spawn_blocking(move || {
+        let mut frames = app_state.camera.subscribe();
+
+        while running_thread.load(Ordering::Relaxed) {
+            match runtime.block_on(tokio::time::timeout(FRAME_WAIT, frames.changed())) {
+                Ok(Ok(())) => {}
+                Ok(Err(_)) => break, // camera state dropped
+                Err(_) => continue,  // no new frame yet
+            }
+
+            let Some(frame) = frames.borrow_and_update().clone() else {
+                continue;
+            };
+
+            let mode = app_state.state.read().unwrap().mode;
+
+            for detector in detectors.iter_mut().filter(|d| d.runs_in(mode)) {
+                match detector.detect(&frame.mat) {
+                    Ok(detections) => {
+                        for event in detections.events {
+                            app_state.bus.publish(event);
+                        }
+
+                        app_state
+                            .overlay
+                            .set(detector.name(), detections.annotations);
+                    }
+                    Err(e) => eprintln!("{} detector failed: {e:#}", detector.name()),
+                }
+            }
+        }
+    });
+
+    while let Ok(event) = bus_rx.recv().await {
+        if matches!(event, Event::Shutdown) {
+            println!("Vision node shutting down");
+            break;
+        }
+    }
+
+    running.store(false, Ordering::Relaxed);
+    let _ = task.await;
+}
@@ -1,27 +1,33 @@
-use axum::body::{Body, Bytes};
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::{Json, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::response::Response;
-use axum::{Router, response::Html, routing::get, routing::post};
-use futures::stream;
+use axum::{Router, routing::get, routing::post};
+use axum_htmx::HxResponseTrigger;
+use maud::Markup;
 use serde::Deserialize;
 use serde::Serialize;
-use std::convert::Infallible;
 use std::sync::Arc;
-use std::time::Duration;
 use tokio::sync::broadcast;
-use tokio::time::sleep;
 use tower_http::cors::CorsLayer;
-use tower_http::services::ServeDir;
-use tower_http::services::ServeFile;
 
 use crate::AppState;
+use crate::behaviour;
 use crate::bus::event::{Event, Mode, ModeCommand, MotorCommand, MotorDirection, ServoCommand};
 use crate::bus::event_bus::EventBus;
 use crate::nodes::telemetry_bridge::TelemetryTx;
 
+mod assets;
+mod camera;
+mod map;
+mod mjpeg;
+mod odometry;
+mod scan;
+mod script;
+mod snapshots;
+mod views;
+
 #[derive(Debug, Deserialize)]
 struct WebCommand {
     action: String,
@@ -47,21 +53,64 @@ pub struct ErrorResponse {
     error: String,
 }
 
-pub async fn run(app_state: AppState) {
-    let static_files = ServeDir::new("static");
+/// JSON error body with the given status
+pub fn error(status: StatusCode, message: &str) -> Response {
+    (
+        status,
+        Json(ErrorResponse {
+            error: message.to_string(),
+        }),
+    )
+        .into_response()
+}
 
+pub async fn run(app_state: AppState) {
     let app = Router::new()
         .route("/ws", get(ws_handler))
         .route("/", get(index))
-        .nest_service("/static", static_files)
-        .nest_service("/camera/frame.jpg", ServeFile::new("/tmp/frame.jpg"))
+        .route("/static/{*path}", get(assets::static_file))
+        .route("/camera/frame.jpg", get(assets::camera_frame))
         .route("/time", get(time))
         .route("/partials/camera", get(partial_camera))
-        .route("/camera/frame.mjpeg", get(mjpeg_handler))
+        .route("/camera/frame.mjpeg", get(mjpeg::mjpeg_handler))
+        .route("/partials/mode", get(partial_mode))
+        .route("/partials/servo", get(partial_servo))
         .route("/partials/sensors", get(partial_sensors))
+        .route("/partials/leds", get(partial_leds))
+        .route("/partials/scan", get(partial_scan))
+        .route("/partials/map", get(partial_map))
+        .route("/partials/recording", get(partial_recording))
+        .route("/partials/health", get(partial_health))
         .route("/api/motor", post(motor_command))
         .route("/api/servo", post(servo_command))
         .route("/api/mode", post(mode_command))
+        .route("/api/map", get(map::get_map).delete(map::clear_map))
+        .route("/api/map/save", post(map::save_map))
+        .route("/api/map/load", post(map::load_map))
+        .route("/api/odometry", get(odometry::get_pose))
+        .route("/api/odometry/reset", post(odometry::reset))
+        .route("/api/odometry/calibrate", post(odometry::calibrate))
+        .route("/api/scan", get(scan::get_scan).post(scan::start_scan))
+        .route(
+            "/api/script",
+            get(script::get_script).put(script::put_script),
+        )
+        .route(
+            "/api/camera/settings",
+            get(camera::get_settings).post(camera::update_settings),
+        )
+        .route("/api/camera/record/start", post(camera::start_recording))
+        .route("/api/camera/record/stop", post(camera::stop_recording))
+        .route("/api/camera/snapshot", post(snapshots::take_snapshot))
+        .route("/api/camera/snapshots", get(snapshots::list_snapshots))
+        .route(
+            "/api/camera/snapshots/{id}",
+            get(snapshots::get_snapshot).delete(snapshots::delete_snapshot),
+        )
+        .route(
+            "/api/camera/snapshots/{id}/meta",
+            get(snapshots::get_snapshot_meta),
+        )
         .layer(CorsLayer::permissive())
         .with_state(app_state.clone());
 
@@ -86,41 +135,48 @@ async fn shutdown_signal(bus: Arc<EventBus>) {
     }
 }
 
-async fn index() -> Html<String> {
-    let html =
-        std::fs::read_to_string("templates/index.html").expect("missing templates/index.html");
+async fn index(State(app_state): State<AppState>) -> Markup {
+    let state = app_state.state.read().unwrap();
 
-    Html(html)
+    views::page(&state, app_state.camera.latest().as_deref())
 }
 
 async fn time() -> impl IntoResponse {
     chrono::Utc::now().format("%H:%M:%S").to_string()
 }
 
-// TODO convert this to read sensor data from bus and publish over websockets
-async fn partial_sensors() -> Html<String> {
-    let ldr_left = 0;
-    let ldr_middle = 0;
-    let ldr_right = 0;
-    let ultrasound = 0;
-    let neopixel_r = 0;
-    let neopixel_g = 0;
-    let neopixel_b = 0;
-
-    let html = format!(
-        r#"
-        <ul>
-            <li><strong>LDR Left:</strong> {}</li>
-            <li><strong>LDR Middle:</strong> {}</li>
-            <li><strong>LDR Right:</strong> {}</li>
-            <li><strong>Ultrasound:</strong> {} cm</li>
-            <li><strong>Neopixel RGB:</strong> {}, {}, {}</li>
-        </ul>
-        "#,
-        ldr_left, ldr_middle, ldr_right, ultrasound, neopixel_r, neopixel_g, neopixel_b,
-    );
-
-    Html(html)
+async fn partial_mode(State(app_state): State<AppState>) -> Markup {
+    views::mode(&app_state.state.read().unwrap())
+}
+
+async fn partial_servo(State(app_state): State<AppState>) -> Markup {
+    views::servo(&app_state.state.read().unwrap())
+}
+
+async fn partial_sensors(State(app_state): State<AppState>) -> Markup {
+    views::sensors(&app_state.state.read().unwrap())
+}
+
+async fn partial_leds(State(app_state): State<AppState>) -> Markup {
+    views::leds(&app_state.state.read().unwrap())
+}
+
+async fn partial_scan(State(app_state): State<AppState>) -> Markup {
+    views::scan(&app_state.state.read().unwrap())
+}
+
+async fn partial_map(State(app_state): State<AppState>) -> Markup {
+    views::map(&app_state.state.read().unwrap())
+}
+
+async fn partial_recording(State(app_state): State<AppState>) -> Markup {
+    views::recording(&app_state.state.read().unwrap())
+}
+
+async fn partial_health(State(app_state): State<AppState>) -> Markup {
+    let state = app_state.state.read().unwrap();
+
+    views::health(&state, app_state.camera.latest().as_deref())
 }
 
 async fn partial_camera() -> impl IntoResponse {
@@ -154,13 +210,7 @@ async fn motor_command(
             )
                 .into_response()
         }
-        Err(err) => (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )
-            .into_response(),
+        Err(err) => error(StatusCode::BAD_REQUEST, err),
     }
 }
 
@@ -190,15 +240,14 @@ async fn servo_command(
     match servo_state {
         Ok(angle) => {
             servo_handler(app_state, angle);
-            (StatusCode::OK, Json(ServoResponse { angle })).into_response()
+            (
+                StatusCode::OK,
+                HxResponseTrigger::normal(["servo-changed"]),
+                Json(ServoResponse { angle }),
+            )
+                .into_response()
         }
-        Err(err) => (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )
-            .into_response(),
+        Err(err) => error(StatusCode::BAD_REQUEST, err),
     }
 }
 
@@ -208,24 +257,28 @@ async fn mode_command(
 ) -> Response {
     println!("Received mode command {:?}", payload);
 
+    // `mode.<name>` runs the registered behaviour of that name
     let mode = match payload.action.as_str() {
         "mode.manual" => Ok(Mode::Manual),
-        "mode.automatic" => Ok(Mode::Automatic),
-        _ => Err("Unknown mode"),
+        "mode.automatic" => Ok(Mode::Automatic(behaviour::DEFAULT)),
+        action => action
+            .strip_prefix("mode.")
+            .and_then(behaviour::lookup)
+            .map(Mode::Automatic)
+            .ok_or("Unknown mode"),
     };
 
     match mode {
         Ok(mode) => {
             mode_handler(app_state, mode);
-            (StatusCode::OK, Json(ModeResponse { mode })).into_response()
+            (
+                StatusCode::OK,
+                HxResponseTrigger::normal(["mode-changed"]),
+                Json(ModeResponse { mode }),
+            )
+                .into_response()
         }
-        Err(err) => (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: err.to_string(),
-            }),
-        )
-            .into_response(),
+        Err(err) => error(StatusCode::BAD_REQUEST, err),
     }
 }
 
@@ -265,7 +318,13 @@ fn motor_right_handler(app_state: AppState) {
     app_state.bus.publish(Event::MotorCommand(cmd));
 }
 
+// Doubles as the emergency stop: a running behaviour or script is stopped
+// too, rather than driving off again on its next tick
 fn motor_stop_handler(app_state: AppState) {
+    if app_state.state.read().unwrap().mode != Mode::Manual {
+        mode_handler(app_state.clone(), Mode::Manual);
+    }
+
     let cmd = MotorCommand {
         direction: MotorDirection::Stop,
         speed: 0,
@@ -287,49 +346,6 @@ fn mode_handler(app_state: AppState, mode: Mode) -> Mode {
     mode
 }
 
-async fn mjpeg_handler(State(app_state): State<AppState>) -> impl IntoResponse {
-    let stream = stream::unfold((), move |_| {
-        let frame = app_state.camera.latest_frame.clone();
-        let mut shutdown = app_state.shutdown.clone();
-
-        async move {
-            tokio::select! {
-                _=shutdown.changed()=>{
-                    println!("MJPEG stream shutting down");
-                    None
-                }
-
-                _ = sleep(Duration::from_millis(50)) => {
-                    let jpeg = {
-                        let guard = frame.lock().unwrap();
-                        if guard.is_empty() {
-                            None
-                        } else {
-                            Some(guard.clone())
-                        }
-                    }?;
-
-                    let mut chunk = Vec::new();
-                    chunk.extend_from_slice(b"--frame\r\n");
-                    chunk.extend_from_slice(b"Content-Type: image/jpeg\r\n");
-                    chunk.extend_from_slice(
-                        format!("Content-Length: {}\r\n\r\n", jpeg.len()).as_bytes(),
-                    );
-                    chunk.extend_from_slice(&jpeg);
-                    chunk.extend_from_slice(b"\r\n");
-
-                    Some((Ok::<Bytes, Infallible>(Bytes::from(chunk)), ()))
-                }
-            }
-        }
-    });
-
-    Response::builder()
-        .header("Content-Type", "multipart/x-mixed-replace; boundary=frame")
-        .body(Body::from_stream(stream))
-        .unwrap()
-}
-
 pub async fn ws_handler(
     ws: WebSocketUpgrade,
     State(app_state): State<AppState>,
@@ -0,0 +1,111 @@
+use std::path::{Component, Path, PathBuf};
+
+use axum::extract::{Path as UrlPath, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use rust_embed::Embed;
+
+use crate::AppState;
+
+// Directory whose files take precedence over the embedded ones, so the UI can
+// be tweaked without rebuilding
+const OVERRIDE_DIR_ENV: &str = "HELLO_ROBOT_STATIC_DIR";
+
+const EMBEDDED_CACHE_CONTROL: &str = "public, max-age=3600, must-revalidate";
+
+#[derive(Embed)]
+#[folder = "static/"]
+struct StaticAssets;
+
+pub async fn static_file(UrlPath(path): UrlPath<String>, headers: HeaderMap) -> Response {
+    if let Some(response) = override_file(&path).await {
+        return response;
+    }
+
+    let Some(file) = StaticAssets::get(&path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let etag = etag(&file.metadata.sha256_hash());
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag));
+
+    if not_modified {
+        return (
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, EMBEDDED_CACHE_CONTROL.to_string()),
+            ],
+        )
+            .into_response();
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, file.metadata.mimetype().to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, EMBEDDED_CACHE_CONTROL.to_string()),
+        ],
+        file.data.into_owned(),
+    )
+        .into_response()
+}
+
+// Serves the latest camera frame from memory
+pub async fn camera_frame(State(app_state): State<AppState>) -> Response {
+    let Some(frame) = app_state.camera.latest() else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/jpeg"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        frame.jpeg.clone(),
+    )
+        .into_response()
+}
+
+async fn override_file(path: &str) -> Option<Response> {
+    let dir = std::env::var_os(OVERRIDE_DIR_ENV)?;
+    let file = safe_join(Path::new(&dir), path)?;
+    let data = tokio::fs::read(&file).await.ok()?;
+
+    let mime = mime_guess::from_path(&file).first_or_octet_stream();
+
+    Some(
+        (
+            [
+                (header::CONTENT_TYPE, mime.to_string()),
+                (header::CACHE_CONTROL, "no-cache".to_string()),
+            ],
+            data,
+        )
+            .into_response(),
+    )
+}
+
+// Joins a request path onto `root`, refusing anything that could escape it
+fn safe_join(root: &Path, path: &str) -> Option<PathBuf> {
+    let path = Path::new(path);
+
+    if path
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    Some(root.join(path))
+}
+
+fn etag(hash: &[u8; 32]) -> String {
+    let hex: String = hash[..8].iter().map(|b| format!("{b:02x}")).collect();
+
+    format!("\"{hex}\"")
+}
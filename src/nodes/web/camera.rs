@@ -0,0 +1,73 @@
+use axum::extract::{Json, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum_htmx::HxResponseTrigger;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::error;
+use crate::AppState;
+use crate::bus::event::{Event, RecordCommand};
+use crate::hal::camera::CameraSettings;
+
+#[derive(Serialize)]
+pub struct RecordResponse {
+    recording: bool,
+}
+
+pub async fn get_settings(State(app_state): State<AppState>) -> Json<CameraSettings> {
+    Json(app_state.camera.settings())
+}
+
+// Applies a partial update: only the fields present in the body change
+pub async fn update_settings(
+    State(app_state): State<AppState>,
+    Json(patch): Json<Value>,
+) -> Response {
+    println!("Received camera settings {patch}");
+
+    match merge_settings(app_state.camera.settings(), patch) {
+        Ok(settings) => {
+            app_state.camera.update_settings(settings.clone());
+            (StatusCode::OK, Json(settings)).into_response()
+        }
+        Err(err) => error(StatusCode::BAD_REQUEST, &format!("{err:#}")),
+    }
+}
+
+fn merge_settings(current: CameraSettings, patch: Value) -> anyhow::Result<CameraSettings> {
+    let Value::Object(patch) = patch else {
+        anyhow::bail!("Expected a JSON object");
+    };
+
+    let mut merged = serde_json::to_value(current)?;
+
+    if let Value::Object(fields) = &mut merged {
+        fields.extend(patch);
+    }
+
+    let settings: CameraSettings = serde_json::from_value(merged)?;
+    settings.validate()?;
+
+    Ok(settings)
+}
+
+pub async fn start_recording(State(app_state): State<AppState>) -> impl IntoResponse {
+    record_handler(app_state, true)
+}
+
+pub async fn stop_recording(State(app_state): State<AppState>) -> impl IntoResponse {
+    record_handler(app_state, false)
+}
+
+fn record_handler(app_state: AppState, recording: bool) -> impl IntoResponse {
+    println!("Received record command {recording}");
+
+    let cmd = RecordCommand { recording };
+    app_state.bus.publish(Event::RecordCommand(cmd));
+
+    (
+        HxResponseTrigger::normal(["recording-changed"]),
+        Json(RecordResponse { recording }),
+    )
+}
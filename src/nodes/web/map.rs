@@ -0,0 +1,106 @@
+use axum::Json;
+use axum::extract::{Query, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum_htmx::HxResponseTrigger;
+use serde::Deserialize;
+
+use super::error;
+use crate::AppState;
+use crate::mapping::grid::OccupancyGrid;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MapFormat {
+    #[default]
+    Json,
+    Png,
+}
+
+/// `/api/map?format=png` for a picture of the grid instead of its cells
+#[derive(Debug, Deserialize)]
+pub struct MapParams {
+    #[serde(default)]
+    format: MapFormat,
+}
+
+pub async fn get_map(
+    State(app_state): State<AppState>,
+    Query(params): Query<MapParams>,
+) -> Response {
+    let grid = app_state.map.read().unwrap();
+
+    match params.format {
+        MapFormat::Json => Json(&*grid).into_response(),
+        MapFormat::Png => match grid.to_png() {
+            Ok(png) => ([(header::CONTENT_TYPE, "image/png")], png).into_response(),
+            Err(err) => error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Failed to encode map: {err:#}"),
+            ),
+        },
+    }
+}
+
+// Starts a new, empty map. The robot's pose carries on from where it is.
+pub async fn clear_map(State(app_state): State<AppState>) -> Response {
+    *app_state.map.write().unwrap() = OccupancyGrid::new(&app_state.config.map);
+
+    println!("Cleared map");
+
+    (
+        StatusCode::NO_CONTENT,
+        HxResponseTrigger::normal(["map-changed"]),
+        (),
+    )
+        .into_response()
+}
+
+pub async fn save_map(State(app_state): State<AppState>) -> Response {
+    let path = &app_state.config.map.path;
+    let json = serde_json::to_vec(&*app_state.map.read().unwrap());
+
+    let result = async { anyhow::Ok(tokio::fs::write(path, json?).await?) }.await;
+
+    match result {
+        Ok(()) => {
+            println!("Saved map to {}", path.display());
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(err) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to save map: {err:#}"),
+        ),
+    }
+}
+
+// Replaces the current map with the one last saved
+pub async fn load_map(State(app_state): State<AppState>) -> Response {
+    let path = &app_state.config.map.path;
+
+    let result = async {
+        let grid: OccupancyGrid = serde_json::from_slice(&tokio::fs::read(path).await?)?;
+        grid.validate()?;
+
+        anyhow::Ok(grid)
+    }
+    .await;
+
+    match result {
+        Ok(grid) => {
+            *app_state.map.write().unwrap() = grid;
+            println!("Loaded map from {}", path.display());
+
+            (
+                StatusCode::NO_CONTENT,
+                HxResponseTrigger::normal(["map-changed"]),
+                (),
+            )
+                .into_response()
+        }
+        Err(err) => error(
+            StatusCode::BAD_REQUEST,
+            &format!("Failed to load map: {err:#}"),
+        ),
+    }
+}
@@ -0,0 +1,209 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::body::{Body, Bytes};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use futures::{StreamExt, stream};
+use serde::Deserialize;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use super::error;
+use crate::AppState;
+use crate::hal::camera::{Frame, FrameRx, Variant};
+use crate::vision::overlay::OverlayViewer;
+
+// Narrowest stream a client may ask for
+const MIN_WIDTH: i32 = 64;
+
+/// Per-client stream options, e.g. `/camera/frame.mjpeg?fps=5&width=320&quality=50`
+#[derive(Debug, Deserialize)]
+pub struct StreamParams {
+    /// `1` draws the HUD and detector boxes on the stream
+    #[serde(default)]
+    overlay: u8,
+    /// At most this many frames per second, up to the camera's own rate
+    fps: Option<f64>,
+    /// Scaled down to this width in pixels, never up
+    width: Option<i32>,
+    /// JPEG quality, 1..=100
+    quality: Option<i32>,
+}
+
+impl StreamParams {
+    fn validate(&self) -> Result<(), String> {
+        if let Some(fps) = self.fps
+            && !(fps > 0.0 && fps <= 60.0)
+        {
+            return Err(format!("fps must be between 0 and 60, got {fps}"));
+        }
+
+        if let Some(width) = self.width
+            && width < MIN_WIDTH
+        {
+            return Err(format!("width must be at least {MIN_WIDTH}, got {width}"));
+        }
+
+        if let Some(quality) = self.quality
+            && !(1..=100).contains(&quality)
+        {
+            return Err(format!("quality must be between 1 and 100, got {quality}"));
+        }
+
+        Ok(())
+    }
+}
+
+// One connected MJPEG viewer. Wakes only when a new frame is published and
+// counts the frames it missed by being too slow.
+//
+// Frames are pulled one at a time, only once the previous one has been
+// handed to the socket, and the watch channel holds just the latest frame.
+// A client on a slow link therefore skips straight to the newest frame
+// instead of queueing stale ones.
+struct MjpegClient {
+    frames: FrameRx,
+    shutdown: watch::Receiver<()>,
+    // Keeps the camera node rendering the overlay while this client wants it
+    _viewer: Option<OverlayViewer>,
+    variant: Variant,
+    // Minimum time between frames when the client asked for a lower fps
+    interval: Option<Duration>,
+    last_sent: Option<Instant>,
+    last_seq: Option<u64>,
+    sent: u64,
+    dropped: u64,
+}
+
+impl MjpegClient {
+    async fn next_frame(&mut self) -> Option<Arc<Frame>> {
+        // Frames published while the last one was still being sent were
+        // missed because the client couldn't keep up. The newest of them is
+        // still to come, and frames skipped to keep to a lower fps below
+        // were never wanted, so neither counts.
+        if let Some(last_seq) = self.last_seq
+            && let Some(latest) = self.frames.borrow().as_ref().map(|frame| frame.seq)
+        {
+            self.dropped += latest.saturating_sub(last_seq + 1);
+        }
+
+        if let (Some(interval), Some(last_sent)) = (self.interval, self.last_sent) {
+            tokio::select! {
+                _ = self.shutdown.changed() => return None,
+                _ = tokio::time::sleep_until(last_sent + interval) => {}
+            }
+        }
+
+        loop {
+            tokio::select! {
+                _ = self.shutdown.changed() => {
+                    println!("MJPEG stream shutting down");
+                    return None;
+                }
+
+                changed = self.frames.changed() => {
+                    changed.ok()?;
+
+                    let Some(frame) = self.frames.borrow_and_update().clone() else {
+                        continue;
+                    };
+
+                    self.last_seq = Some(frame.seq);
+                    self.last_sent = Some(Instant::now());
+                    self.sent += 1;
+
+                    return Some(frame);
+                }
+            }
+        }
+    }
+
+    // Multipart part for `frame`. The JPEG bytes are shared, not copied, and
+    // scaled encodings are made once per frame for all clients that want them.
+    fn part(&self, frame: &Frame) -> Option<[Bytes; 3]> {
+        let jpeg = match tokio::task::block_in_place(|| frame.encoded(self.variant)) {
+            Ok(jpeg) => jpeg,
+            Err(e) => {
+                eprintln!("MJPEG encode failed: {e:#}");
+                return None;
+            }
+        };
+
+        let headers = format!(
+            "--frame\r\n\
+             Content-Type: image/jpeg\r\n\
+             Content-Length: {}\r\n\
+             X-Frame-Sequence: {}\r\n\
+             X-Frame-Timestamp: {}\r\n\
+             X-Frame-Size: {}x{}\r\n\
+             X-Dropped-Frames: {}\r\n\r\n",
+            jpeg.len(),
+            frame.seq,
+            frame.captured_at.to_rfc3339(),
+            frame.width,
+            frame.height,
+            self.dropped,
+        );
+
+        Some([Bytes::from(headers), jpeg, Bytes::from_static(b"\r\n")])
+    }
+}
+
+impl Drop for MjpegClient {
+    fn drop(&mut self) {
+        println!(
+            "MJPEG client disconnected: sent {} frames, dropped {}",
+            self.sent, self.dropped
+        );
+    }
+}
+
+pub async fn mjpeg_handler(
+    State(app_state): State<AppState>,
+    Query(params): Query<StreamParams>,
+) -> Response {
+    if let Err(message) = params.validate() {
+        return error(StatusCode::BAD_REQUEST, &message);
+    }
+
+    let mut frames = app_state.camera.subscribe();
+
+    // Send whatever frame is current straight away rather than waiting for the next
+    frames.mark_changed();
+
+    let overlay = params.overlay == 1;
+    let client = MjpegClient {
+        frames,
+        shutdown: app_state.shutdown.clone(),
+        _viewer: overlay.then(|| app_state.overlay.viewer()),
+        variant: Variant {
+            overlay,
+            width: params.width,
+            quality: params.quality,
+        },
+        interval: params.fps.map(|fps| Duration::from_secs_f64(1.0 / fps)),
+        last_sent: None,
+        last_seq: None,
+        sent: 0,
+        dropped: 0,
+    };
+
+    let stream = stream::unfold(client, |mut client| async move {
+        loop {
+            let frame = client.next_frame().await?;
+
+            if let Some(part) = client.part(&frame) {
+                return Some((part, client));
+            }
+        }
+    })
+    .flat_map(|part| stream::iter(part.map(Ok::<Bytes, Infallible>)));
+
+    Response::builder()
+        .header("Content-Type", "multipart/x-mixed-replace; boundary=frame")
+        .body(Body::from_stream(stream))
+        .unwrap()
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+
+use super::error;
+use crate::AppState;
+use crate::bus::event::{Event, Mode, MotorCommand, MotorDirection};
+
+// Longest calibration run, so a typo doesn't send the robot across the room
+const MAX_CALIBRATION_SECS: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Motion {
+    /// Straight ahead, measured in cm
+    Forward,
+    /// Anticlockwise on the spot, measured in degrees
+    Spin,
+}
+
+/// One step of calibration: without `measured` the robot makes the motion
+/// at full speed for `secs`; with it, the result is stored
+#[derive(Debug, Deserialize)]
+pub struct CalibrationRequest {
+    motion: Motion,
+    secs: f64,
+    measured: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct CalibrationRun {
+    motion: Motion,
+    secs: f64,
+}
+
+pub async fn get_pose(State(app_state): State<AppState>) -> Response {
+    match app_state.state.read().unwrap().pose {
+        Some(pose) => Json(pose).into_response(),
+        None => error(StatusCode::NOT_FOUND, "No pose published yet"),
+    }
+}
+
+// Makes wherever the robot is now the origin, facing along x
+pub async fn reset(State(app_state): State<AppState>) -> StatusCode {
+    app_state.bus.publish(Event::OdometryReset);
+
+    StatusCode::NO_CONTENT
+}
+
+pub async fn calibrate(
+    State(app_state): State<AppState>,
+    Json(request): Json<CalibrationRequest>,
+) -> Response {
+    if !(request.secs > 0.0 && request.secs <= MAX_CALIBRATION_SECS) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            &format!("secs must be between 0 and {MAX_CALIBRATION_SECS}"),
+        );
+    }
+
+    match request.measured {
+        None => run_motion(app_state, request.motion, request.secs).await,
+        Some(measured) => store(app_state, request.motion, request.secs, measured).await,
+    }
+}
+
+async fn run_motion(app_state: AppState, motion: Motion, secs: f64) -> Response {
+    // A behaviour would take the motors straight back
+    if app_state.state.read().unwrap().mode != Mode::Manual {
+        return error(StatusCode::CONFLICT, "Switch to manual mode to calibrate");
+    }
+
+    let direction = match motion {
+        Motion::Forward => MotorDirection::Forward,
+        Motion::Spin => MotorDirection::Left,
+    };
+
+    println!("Calibrating odometry: {motion:?} for {secs}s");
+
+    app_state.bus.publish(Event::MotorCommand(MotorCommand {
+        direction,
+        speed: 100,
+    }));
+
+    // Stopped from its own task, so the robot still stops if the client
+    // gives up waiting and the request is dropped
+    let bus = app_state.bus.clone();
+    let stop = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+        bus.publish(Event::MotorCommand(MotorCommand {
+            direction: MotorDirection::Stop,
+            speed: 0,
+        }));
+    });
+    let _ = stop.await;
+
+    Json(CalibrationRun { motion, secs }).into_response()
+}
+
+// Saves the measured speed alongside the existing coefficients and hands
+// them to the odometry node
+async fn store(app_state: AppState, motion: Motion, secs: f64, measured: f64) -> Response {
+    if measured <= 0.0 {
+        return error(StatusCode::BAD_REQUEST, "measured must be positive");
+    }
+
+    let config = &app_state.config.odometry;
+    let mut calibration = config.load_calibration();
+
+    match motion {
+        Motion::Forward => calibration.speed_cm_s = measured / secs,
+        Motion::Spin => calibration.turn_deg_s = measured / secs,
+    }
+
+    let result = async {
+        tokio::fs::write(&config.calibration, toml::to_string(&calibration)?).await?;
+        anyhow::Ok(())
+    }
+    .await;
+
+    if let Err(err) = result {
+        return error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to save calibration: {err:#}"),
+        );
+    }
+
+    println!(
+        "Saved odometry calibration to {}",
+        config.calibration.display()
+    );
+    app_state
+        .bus
+        .publish(Event::OdometryCalibration(calibration));
+
+    Json(calibration).into_response()
+}
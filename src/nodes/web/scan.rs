@@ -0,0 +1,30 @@
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum_htmx::HxResponseTrigger;
+
+use super::error;
+use crate::AppState;
+use crate::bus::event::Event;
+
+// Latest range scan, as published by the scanner node
+pub async fn get_scan(State(app_state): State<AppState>) -> Response {
+    match app_state.state.read().unwrap().scan.clone() {
+        Some(scan) => Json(scan).into_response(),
+        None => error(StatusCode::NOT_FOUND, "No scan taken yet"),
+    }
+}
+
+// Starts a sweep. The result arrives a few seconds later on the bus, the
+// telemetry socket and GET /api/scan.
+pub async fn start_scan(State(app_state): State<AppState>) -> Response {
+    app_state.bus.publish(Event::ScanCommand);
+
+    (
+        StatusCode::ACCEPTED,
+        HxResponseTrigger::normal(["scan-started"]),
+        (),
+    )
+        .into_response()
+}
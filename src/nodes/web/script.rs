@@ -0,0 +1,43 @@
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+
+use super::error;
+use crate::AppState;
+use crate::behaviour::script;
+
+// Source of the script the `script` behaviour runs
+pub async fn get_script(State(app_state): State<AppState>) -> Response {
+    match tokio::fs::read_to_string(&app_state.config.script.path).await {
+        Ok(source) => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            source,
+        )
+            .into_response(),
+        Err(_) => error(StatusCode::NOT_FOUND, "No script uploaded"),
+    }
+}
+
+// Replaces the script, refusing anything that doesn't compile. Takes effect
+// the next time the `script` behaviour is selected.
+pub async fn put_script(State(app_state): State<AppState>, source: String) -> Response {
+    if let Err(err) = script::check(&source) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            &format!("Script does not compile: {err}"),
+        );
+    }
+
+    let path = &app_state.config.script.path;
+
+    if let Err(err) = tokio::fs::write(path, source).await {
+        return error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to save script: {err}"),
+        );
+    }
+
+    println!("Saved script to {}", path.display());
+
+    StatusCode::NO_CONTENT.into_response()
+}
@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+use axum::extract::{Json, Path, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::error;
+use crate::AppState;
+use crate::bus::event::{Ldr, Led, Mode};
+
+/// Sidecar written next to each snapshot, describing the robot at the
+/// instant the frame was taken
+#[derive(Serialize)]
+struct SnapshotMeta {
+    id: String,
+    captured_at: DateTime<Utc>,
+    frame_seq: u64,
+    width: i32,
+    height: i32,
+    mode: Mode,
+    servo_angle: Option<u8>,
+    ultrasound_cm: Option<f64>,
+    ldr: Option<Ldr>,
+    led: Option<Led>,
+}
+
+pub async fn take_snapshot(State(app_state): State<AppState>) -> Response {
+    let Some(frame) = app_state.camera.latest() else {
+        return error(StatusCode::SERVICE_UNAVAILABLE, "No camera frame available");
+    };
+
+    let meta = {
+        let state = app_state.state.read().unwrap();
+
+        SnapshotMeta {
+            id: frame.captured_at.format("%Y%m%d-%H%M%S-%3f").to_string(),
+            captured_at: frame.captured_at,
+            frame_seq: frame.seq,
+            width: frame.width,
+            height: frame.height,
+            mode: state.mode,
+            servo_angle: state.servo.as_ref().map(|servo| servo.angle),
+            ultrasound_cm: state.ultrasound.as_ref().map(|u| u.distance),
+            ldr: state.ldr.clone(),
+            led: state.led.clone(),
+        }
+    };
+
+    let dir = &app_state.config.snapshots.dir;
+
+    let result = async {
+        tokio::fs::create_dir_all(dir).await?;
+        tokio::fs::write(dir.join(format!("{}.jpg", meta.id)), &frame.jpeg).await?;
+        tokio::fs::write(
+            dir.join(format!("{}.json", meta.id)),
+            serde_json::to_vec_pretty(&meta)?,
+        )
+        .await?;
+
+        anyhow::Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            println!("Saved snapshot {}", meta.id);
+            (StatusCode::CREATED, Json(meta)).into_response()
+        }
+        Err(err) => error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("Failed to save snapshot: {err:#}"),
+        ),
+    }
+}
+
+// Lists the sidecars of every snapshot, oldest first
+pub async fn list_snapshots(State(app_state): State<AppState>) -> Response {
+    let dir = &app_state.config.snapshots.dir;
+
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return Json(Vec::<Value>::new()).into_response();
+    };
+
+    let mut snapshots = Vec::new();
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+
+        if let Ok(bytes) = tokio::fs::read(&path).await
+            && let Ok(meta) = serde_json::from_slice::<Value>(&bytes)
+        {
+            snapshots.push(meta);
+        }
+    }
+
+    snapshots.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+
+    Json(snapshots).into_response()
+}
+
+pub async fn get_snapshot(State(app_state): State<AppState>, Path(id): Path<String>) -> Response {
+    let Some(path) = snapshot_path(&app_state, &id, "jpg") else {
+        return error(StatusCode::BAD_REQUEST, "Invalid snapshot id");
+    };
+
+    match tokio::fs::read(path).await {
+        Ok(jpeg) => ([(header::CONTENT_TYPE, "image/jpeg")], jpeg).into_response(),
+        Err(_) => error(StatusCode::NOT_FOUND, "Snapshot not found"),
+    }
+}
+
+pub async fn get_snapshot_meta(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+) -> Response {
+    let Some(path) = snapshot_path(&app_state, &id, "json") else {
+        return error(StatusCode::BAD_REQUEST, "Invalid snapshot id");
+    };
+
+    match tokio::fs::read(path).await {
+        Ok(json) => ([(header::CONTENT_TYPE, "application/json")], json).into_response(),
+        Err(_) => error(StatusCode::NOT_FOUND, "Snapshot not found"),
+    }
+}
+
+pub async fn delete_snapshot(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+) -> Response {
+    let (Some(jpeg), Some(meta)) = (
+        snapshot_path(&app_state, &id, "jpg"),
+        snapshot_path(&app_state, &id, "json"),
+    ) else {
+        return error(StatusCode::BAD_REQUEST, "Invalid snapshot id");
+    };
+
+    if tokio::fs::remove_file(jpeg).await.is_err() {
+        return error(StatusCode::NOT_FOUND, "Snapshot not found");
+    }
+
+    let _ = tokio::fs::remove_file(meta).await;
+
+    println!("Deleted snapshot {id}");
+
+    StatusCode::NO_CONTENT.into_response()
+}
+
+// Snapshot ids are timestamps; anything else could escape the gallery dir
+fn snapshot_path(app_state: &AppState, id: &str, extension: &str) -> Option<PathBuf> {
+    let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_digit() || c == '-');
+
+    valid.then(|| {
+        app_state
+            .config
+            .snapshots
+            .dir
+            .join(format!("{id}.{extension}"))
+    })
+}
@@ -0,0 +1,411 @@
+use chrono::Utc;
+use maud::{DOCTYPE, Markup, html};
+
+use crate::behaviour;
+use crate::bus::{event::Mode, state::RobotState};
+use crate::hal::camera::Frame;
+
+// Nodes listed on the health panel, in display order
+const NODES: [&str; 6] = ["motor", "servo", "ultrasound", "ldr", "leds", "camera"];
+
+// Distance at the edge of the radar plot, cm. Further readings sit on the rim.
+const RADAR_RANGE: f64 = 100.0;
+
+// A node that hasn't been heard from for this long is shown as stale
+const STALE_AFTER_SECS: i64 = 5;
+
+pub fn page(state: &RobotState, frame: Option<&Frame>) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1";
+                title { "Rusty Robot Dashboard" }
+                link rel="stylesheet" href="/static/pico.min.css";
+                link rel="stylesheet" href="/static/robot.css";
+                link
+                    rel="stylesheet"
+                    href="https://cdn.jsdelivr.net/npm/bootstrap-icons@1.13.1/font/bootstrap-icons.min.css";
+                script src="https://unpkg.com/htmx.org@2.0.4" {}
+                script src="https://unpkg.com/htmx-ext-json-enc@2.0.1/json-enc.js" {}
+            }
+            body hx-ext="json-enc" {
+                main class="hud" {
+                    img class="camera" src="/camera/frame.mjpeg?overlay=1" alt="Robot camera";
+
+                    // Swaps between the annotated and raw stream
+                    label class="overlay-toggle" {
+                        input
+                            type="checkbox"
+                            role="switch"
+                            checked
+                            onchange="document.querySelector('img.camera').src = this.checked ? '/camera/frame.mjpeg?overlay=1' : '/camera/frame.mjpeg'";
+                        "Overlay"
+                    }
+
+                    aside class="panels" {
+                        (mode(state))
+                        (motor_pad())
+                        (servo(state))
+                        (sensors(state))
+                        (scan(state))
+                        (map(state))
+                        (leds(state))
+                        (recording(state))
+                        (health(state, frame))
+                    }
+
+                    div class="branding" { "Hello, Robot! © 2026" }
+                }
+            }
+        }
+    }
+}
+
+// Self-refreshing panel, re-fetched from /partials/<name> on `trigger`
+fn panel(name: &str, trigger: &str, body: Markup) -> Markup {
+    html! {
+        section
+            id=(name)
+            class="panel"
+            hx-get=(format!("/partials/{name}"))
+            hx-trigger=(trigger)
+            hx-swap="outerHTML"
+        {
+            div class="heading" { (name) }
+            (body)
+        }
+    }
+}
+
+// Button that posts `{"action": <action>}` to an API endpoint and ignores the reply
+fn action_button(url: &str, action: &str, icon: &str, active: bool) -> Markup {
+    html! {
+        button.action.active[active]
+            hx-post=(url)
+            hx-vals=(format!(r#"{{"action": "{action}"}}"#))
+            hx-swap="none"
+            title=(action)
+        {
+            i class={ "bi " (icon) } {}
+        }
+    }
+}
+
+pub fn mode(state: &RobotState) -> Markup {
+    panel(
+        "mode",
+        "every 2s, mode-changed from:body",
+        html! {
+            div class="buttons" {
+                (action_button("/api/mode", "mode.manual", "bi-person-fill-gear", state.mode == Mode::Manual))
+                @for (name, _) in behaviour::REGISTRY {
+                    (action_button("/api/mode", &format!("mode.{name}"), behaviour_icon(name), state.mode == Mode::Automatic(name)))
+                }
+            }
+            div class="value" {
+                (state.mode)
+                @if let Some(behaviour) = &state.behaviour {
+                    " · " (behaviour.to)
+                }
+            }
+        },
+    )
+}
+
+// Behaviours without an icon of their own get a generic robot
+fn behaviour_icon(name: &str) -> &'static str {
+    match name {
+        "wander" => "bi-ev-front-fill",
+        "line-follow" => "bi-bezier2",
+        "wall-follow" => "bi-bricks",
+        "sentry" => "bi-shield-fill-exclamation",
+        "mission" => "bi-diagram-3-fill",
+        "script" => "bi-code-slash",
+        _ => "bi-robot",
+    }
+}
+
+// Motor buttons drive while held and stop on release
+pub fn motor_pad() -> Markup {
+    let stop = "htmx.ajax('POST', '/api/motor', {source: this, values: {action: 'motor.stop'}, swap: 'none'})";
+
+    html! {
+        section id="motor" class="panel" {
+            div class="heading" { "motor" }
+            div class="pad" {
+                @for (action, icon) in [
+                    ("motor.forward", "bi-arrow-up-circle-fill"),
+                    ("motor.left", "bi-arrow-left-circle-fill"),
+                    ("motor.stop", "bi-stop-circle-fill"),
+                    ("motor.right", "bi-arrow-right-circle-fill"),
+                    ("motor.backward", "bi-arrow-down-circle-fill"),
+                ] {
+                    button
+                        class={ "action " (action.replace('.', "-")) }
+                        hx-post="/api/motor"
+                        hx-trigger="mousedown, touchstart"
+                        hx-vals=(format!(r#"{{"action": "{action}"}}"#))
+                        hx-swap="none"
+                        hx-on-mouseup=(stop)
+                        hx-on-touchend=(stop)
+                        title=(action)
+                    {
+                        i class={ "bi " (icon) } {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn servo(state: &RobotState) -> Markup {
+    let angle = state.servo.as_ref().map(|s| s.angle);
+
+    panel(
+        "servo",
+        "every 1s, servo-changed from:body",
+        html! {
+            div class="buttons" {
+                (action_button("/api/servo", "servo.end", "bi-arrow-up-circle-fill", angle == Some(170)))
+                (action_button("/api/servo", "servo.start", "bi-arrow-down-circle-fill", angle == Some(10)))
+            }
+            div class="value" {
+                @match angle {
+                    Some(angle) => { (angle) "°" }
+                    None => "—",
+                }
+            }
+        },
+    )
+}
+
+pub fn sensors(state: &RobotState) -> Markup {
+    let distance = state.ultrasound.as_ref().map(|u| u.distance);
+
+    panel(
+        "sensors",
+        "every 500ms",
+        html! {
+            div class="reading" {
+                span { "Ultrasound" }
+                strong {
+                    @match distance {
+                        Some(distance) => { (format!("{distance:.1}")) " cm" }
+                        None => "—",
+                    }
+                }
+            }
+            progress value=(distance.unwrap_or(0.0).clamp(0.0, 100.0)) max="100" {}
+
+            div class="ldr" {
+                @if let Some(ldr) = &state.ldr {
+                    @for (label, value) in [("L", ldr.l_val), ("M", ldr.m_val), ("R", ldr.r_val)] {
+                        span.dot.active[value == 1] { (label) }
+                    }
+                } @else {
+                    span { "No LDR readings" }
+                }
+            }
+        },
+    )
+}
+
+// Radar plot of the last scan, straight ahead pointing up
+pub fn scan(state: &RobotState) -> Markup {
+    // Plot centre and radius in SVG units
+    let (cx, cy, r) = (100.0, 100.0, 90.0);
+    let point = |bearing: f64, distance: f64| {
+        let scale = r * distance.clamp(0.0, RADAR_RANGE) / RADAR_RANGE;
+        let theta = bearing.to_radians();
+        (cx + scale * theta.sin(), cy - scale * theta.cos())
+    };
+    let arc = |radius: f64| {
+        format!(
+            "M {} {cy} A {radius} {radius} 0 0 1 {} {cy}",
+            cx - radius,
+            cx + radius
+        )
+    };
+
+    panel(
+        "scan",
+        "every 2s, scan-started from:body",
+        html! {
+            svg class="radar" viewBox="0 0 200 105" {
+                @for ring in 1..=4 {
+                    path class="ring" d=(arc(r * ring as f64 / 4.0)) {}
+                }
+                line class="ring" x1=(cx) y1=(cy) x2=(cx) y2=(cy - r) {}
+
+                @if let Some(scan) = &state.scan {
+                    @let points: Vec<_> = scan
+                        .bearings
+                        .iter()
+                        .zip(&scan.distances)
+                        .map(|(&bearing, &distance)| point(bearing, distance))
+                        .collect();
+
+                    polygon class="free" points=(
+                        std::iter::once((cx, cy))
+                            .chain(points.iter().copied())
+                            .map(|(x, y)| format!("{x:.1},{y:.1}"))
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                    ) {}
+                    @for (x, y) in &points {
+                        circle class="echo" cx=(format!("{x:.1}")) cy=(format!("{y:.1}")) r="2" {}
+                    }
+                }
+            }
+            div class="buttons" {
+                (action_button("/api/scan", "scan.start", "bi-radar", false))
+            }
+            div class="value" {
+                @match &state.scan {
+                    Some(scan) => { "Scanned " (scan.timestamp.format("%H:%M:%S")) }
+                    None => "No scan yet",
+                }
+            }
+        },
+    )
+}
+
+// Occupancy grid built from range scans, re-fetched with the panel
+pub fn map(state: &RobotState) -> Markup {
+    let ts = Utc::now().timestamp_millis();
+
+    panel(
+        "map",
+        "every 5s, map-changed from:body",
+        html! {
+            img class="map" src=(format!("/api/map?format=png&ts={ts}")) alt="Occupancy map";
+            div class="reading" {
+                span { "Pose" }
+                strong {
+                    @match &state.pose {
+                        Some(pose) => {
+                            (format!("{:.0}, {:.0} cm · {:.0}°", pose.x, pose.y, pose.theta.to_degrees()))
+                        }
+                        None => "—",
+                    }
+                }
+            }
+            div class="buttons" {
+                button.action hx-post="/api/map/save" hx-swap="none" title="map.save" {
+                    i class="bi bi-floppy-fill" {}
+                }
+                button.action hx-post="/api/map/load" hx-swap="none" title="map.load" {
+                    i class="bi bi-folder2-open" {}
+                }
+                button.action hx-delete="/api/map" hx-swap="none" title="map.clear" {
+                    i class="bi bi-trash-fill" {}
+                }
+                button.action hx-post="/api/odometry/reset" hx-swap="none" title="odometry.reset" {
+                    i class="bi bi-crosshair" {}
+                }
+            }
+        },
+    )
+}
+
+pub fn leds(state: &RobotState) -> Markup {
+    panel(
+        "leds",
+        "every 1s",
+        html! {
+            @if let Some(led) = &state.led {
+                div class="reading" {
+                    span
+                        class="swatch"
+                        style=(format!("background: rgb({}, {}, {})", led.red, led.green, led.blue))
+                    {}
+                    strong { (led.red) ", " (led.green) ", " (led.blue) }
+                }
+                div class="reading" {
+                    span { "Brightness" }
+                    strong { (led.brightness) }
+                }
+            } @else {
+                div class="reading" { span { "No LED updates" } }
+            }
+        },
+    )
+}
+
+pub fn recording(state: &RobotState) -> Markup {
+    let recording = state.recording.as_ref();
+    let active = recording.is_some_and(|r| r.recording);
+
+    panel(
+        "recording",
+        "every 2s, recording-changed from:body",
+        html! {
+            div class="buttons" {
+                button.action.active[active]
+                    hx-post="/api/camera/record/start"
+                    hx-swap="none"
+                    title="record.start"
+                {
+                    i class="bi bi-record-circle-fill" {}
+                }
+                button.action
+                    hx-post="/api/camera/record/stop"
+                    hx-swap="none"
+                    title="record.stop"
+                {
+                    i class="bi bi-stop-circle-fill" {}
+                }
+                button.action
+                    hx-post="/api/camera/snapshot"
+                    hx-swap="none"
+                    title="snapshot"
+                {
+                    i class="bi bi-camera-fill" {}
+                }
+            }
+            div class="value" {
+                @match recording.and_then(|r| r.segment.as_deref()) {
+                    Some(segment) if active => (segment),
+                    _ if active => "starting",
+                    _ => "stopped",
+                }
+            }
+        },
+    )
+}
+
+pub fn health(state: &RobotState, frame: Option<&Frame>) -> Markup {
+    let now = Utc::now();
+    let last_seen = |node: &str| match node {
+        "camera" => frame.map(|frame| frame.captured_at),
+        _ => state.nodes.get(node).copied(),
+    };
+
+    panel(
+        "health",
+        "every 2s",
+        html! {
+            ul {
+                @for node in NODES {
+                    @let age = last_seen(node).map(|seen| (now - seen).num_seconds());
+                    @let status = match age {
+                        Some(age) if age < STALE_AFTER_SECS => "ok",
+                        Some(_) => "stale",
+                        None => "unknown",
+                    };
+                    li class={ "node " (status) } {
+                        span { (node) }
+                        strong {
+                            @match age {
+                                Some(age) => { (age) "s ago" }
+                                None => "never",
+                            }
+                        }
+                    }
+                }
+            }
+        },
+    )
+}
@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use opencv::{
+    calib3d,
+    core::{Mat, Point, Point2f, Point3f, Rect, Vector},
+    imgproc,
+    objdetect::{
+        self, ArucoDetector, DetectorParameters, PredefinedDictionaryType, RefineParameters,
+    },
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::bus::event::{Event, Fiducial, Pose};
+use crate::hal::camera::CameraSettings;
+use crate::vision::{Annotation, Detections, Detector, calibration::Calibration};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ArucoConfig {
+    pub enabled: bool,
+    /// Marker family, e.g. "4x4_50", "6x6_250", "aruco_original" or "apriltag_36h11"
+    pub dictionary: String,
+    /// Length of a marker's black border edge. Pose translations come out in
+    /// the same unit, metres by convention.
+    pub marker_size: f64,
+    /// OpenCV calibration file for the camera, defaulting to the one the
+    /// camera undistorts with. Without either, markers are reported without
+    /// a pose.
+    pub calibration: Option<PathBuf>,
+}
+
+impl Default for ArucoConfig {
+    fn default() -> Self {
+        ArucoConfig {
+            enabled: false,
+            dictionary: "4x4_50".to_string(),
+            marker_size: 0.05,
+            calibration: None,
+        }
+    }
+}
+
+fn dictionary(name: &str) -> Result<PredefinedDictionaryType> {
+    use PredefinedDictionaryType::*;
+
+    Ok(match name.to_lowercase().as_str() {
+        "4x4_50" => DICT_4X4_50,
+        "4x4_100" => DICT_4X4_100,
+        "4x4_250" => DICT_4X4_250,
+        "4x4_1000" => DICT_4X4_1000,
+        "5x5_50" => DICT_5X5_50,
+        "5x5_100" => DICT_5X5_100,
+        "5x5_250" => DICT_5X5_250,
+        "5x5_1000" => DICT_5X5_1000,
+        "6x6_50" => DICT_6X6_50,
+        "6x6_100" => DICT_6X6_100,
+        "6x6_250" => DICT_6X6_250,
+        "6x6_1000" => DICT_6X6_1000,
+        "7x7_50" => DICT_7X7_50,
+        "7x7_100" => DICT_7X7_100,
+        "7x7_250" => DICT_7X7_250,
+        "7x7_1000" => DICT_7X7_1000,
+        "aruco_original" => DICT_ARUCO_ORIGINAL,
+        "apriltag_16h5" => DICT_APRILTAG_16h5,
+        "apriltag_25h9" => DICT_APRILTAG_25h9,
+        "apriltag_36h10" => DICT_APRILTAG_36h10,
+        "apriltag_36h11" => DICT_APRILTAG_36h11,
+        "aruco_mip_36h12" => DICT_ARUCO_MIP_36h12,
+        _ => bail!("Unknown marker dictionary {name:?}"),
+    })
+}
+
+/// Finds fiducial markers and, given a calibration, estimates their pose
+pub struct MarkerDetector {
+    detector: ArucoDetector,
+    calibration: Option<Calibration>,
+    // Marker corners in its own frame, in the order detection returns them
+    object_points: Vector<Point3f>,
+}
+
+impl MarkerDetector {
+    pub fn new(config: ArucoConfig, camera: &CameraSettings) -> Result<Self> {
+        let dictionary = objdetect::get_predefined_dictionary(dictionary(&config.dictionary)?)?;
+        let detector = ArucoDetector::new(
+            &dictionary,
+            &DetectorParameters::default()?,
+            RefineParameters::new_def()?,
+        )?;
+
+        let calibration = config
+            .calibration
+            .as_deref()
+            .or(camera.calibration.as_deref())
+            .map(Calibration::load)
+            .transpose()
+            .context("Failed to load marker calibration")?;
+
+        // Frames from a calibrated camera arrive with the distortion removed
+        let calibration = match camera.calibration {
+            Some(_) => calibration.map(|calibration| calibration.without_distortion()),
+            None => calibration,
+        };
+
+        let half = (config.marker_size / 2.0) as f32;
+        let object_points = Vector::from_iter([
+            Point3f::new(-half, half, 0.0),
+            Point3f::new(half, half, 0.0),
+            Point3f::new(half, -half, 0.0),
+            Point3f::new(-half, -half, 0.0),
+        ]);
+
+        Ok(MarkerDetector {
+            detector,
+            calibration,
+            object_points,
+        })
+    }
+
+    fn pose(&self, calibration: &Calibration, corners: &Vector<Point2f>) -> Result<Option<Pose>> {
+        let mut rvec = Mat::default();
+        let mut tvec = Mat::default();
+
+        let solved = calib3d::solve_pnp(
+            &self.object_points,
+            corners,
+            &calibration.camera_matrix,
+            &calibration.dist_coeffs,
+            &mut rvec,
+            &mut tvec,
+            false,
+            calib3d::SOLVEPNP_IPPE_SQUARE,
+        )?;
+
+        if !solved {
+            return Ok(None);
+        }
+
+        let rotation = [
+            *rvec.at::<f64>(0)?,
+            *rvec.at::<f64>(1)?,
+            *rvec.at::<f64>(2)?,
+        ];
+        let translation = [
+            *tvec.at::<f64>(0)?,
+            *tvec.at::<f64>(1)?,
+            *tvec.at::<f64>(2)?,
+        ];
+        let [x, y, z] = translation;
+
+        Ok(Some(Pose {
+            translation,
+            rotation,
+            distance: (x * x + y * y + z * z).sqrt(),
+            bearing: x.atan2(z).to_degrees(),
+        }))
+    }
+}
+
+impl Detector for MarkerDetector {
+    fn name(&self) -> &'static str {
+        "aruco"
+    }
+
+    fn detect(&mut self, frame: &Mat) -> Result<Detections> {
+        let mut corners = Vector::<Vector<Point2f>>::new();
+        let mut ids = Vector::<i32>::new();
+        self.detector
+            .detect_markers_def(frame, &mut corners, &mut ids)?;
+
+        // Follow camera resolution changes so the focal length stays right
+        let size = frame.size()?;
+        if let Some(calibration) = &self.calibration
+            && calibration
+                .image_size
+                .is_some_and(|calibrated| calibrated != size)
+        {
+            self.calibration = Some(calibration.scaled_to(size)?);
+        }
+
+        let mut detections = Detections::default();
+
+        for (id, marker) in ids.iter().zip(corners.iter()) {
+            if marker.len() != 4 {
+                continue;
+            }
+
+            let pose = match &self.calibration {
+                Some(calibration) => self.pose(calibration, &marker)?,
+                None => None,
+            };
+
+            let points: Vector<Point> = marker
+                .iter()
+                .map(|p| Point::new(p.x as i32, p.y as i32))
+                .collect();
+            let rect: Rect = imgproc::bounding_rect(&points)?;
+
+            let label = match &pose {
+                Some(pose) => format!("#{id} {:.2}", pose.distance),
+                None => format!("#{id}"),
+            };
+
+            let mut marker_corners = [[0.0; 2]; 4];
+            for (corner, p) in marker_corners.iter_mut().zip(marker.iter()) {
+                *corner = [p.x, p.y];
+            }
+
+            detections.events.push(Event::FiducialDetected(Fiducial {
+                id,
+                corners: marker_corners,
+                pose,
+                frame_width: frame.cols(),
+                frame_height: frame.rows(),
+            }));
+            detections.annotations.push(Annotation {
+                rect,
+                label,
+                colour: (0, 200, 255),
+            });
+        }
+
+        Ok(detections)
+    }
+}
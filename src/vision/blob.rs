@@ -0,0 +1,154 @@
+use anyhow::Result;
+use opencv::{
+    core::{self, Mat, Point, Scalar, Size, Vector},
+    imgproc,
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::bus::event::{Blob, BoundingBox, Event};
+use crate::vision::{Annotation, Detections, Detector};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BlobConfig {
+    pub enabled: bool,
+    /// Name given to the tracked blob, e.g. "ball"
+    pub label: String,
+    /// Lower HSV bound, OpenCV ranges (hue 0..=179, saturation and value 0..=255).
+    /// A hue minimum above the maximum wraps around through red.
+    pub hsv_min: [u8; 3],
+    pub hsv_max: [u8; 3],
+    /// Blobs smaller than this many pixels are ignored as noise
+    pub min_area: f64,
+}
+
+impl Default for BlobConfig {
+    fn default() -> Self {
+        // An orange ball under indoor lighting
+        BlobConfig {
+            enabled: false,
+            label: "ball".to_string(),
+            hsv_min: [5, 120, 120],
+            hsv_max: [20, 255, 255],
+            min_area: 300.0,
+        }
+    }
+}
+
+/// Finds the largest region of a colour by HSV thresholding and contours
+pub struct BlobDetector {
+    config: BlobConfig,
+    kernel: Mat,
+}
+
+impl BlobDetector {
+    pub fn new(config: BlobConfig) -> Result<Self> {
+        let kernel = imgproc::get_structuring_element_def(imgproc::MORPH_ELLIPSE, Size::new(5, 5))?;
+
+        Ok(BlobDetector { config, kernel })
+    }
+
+    fn mask(&self, hsv: &Mat) -> Result<Mat> {
+        let [h_min, s_min, v_min] = self.config.hsv_min.map(f64::from);
+        let [h_max, s_max, v_max] = self.config.hsv_max.map(f64::from);
+
+        let in_range = |h_lo: f64, h_hi: f64| -> Result<Mat> {
+            let mut mask = Mat::default();
+            core::in_range(
+                hsv,
+                &Scalar::new(h_lo, s_min, v_min, 0.0),
+                &Scalar::new(h_hi, s_max, v_max, 0.0),
+                &mut mask,
+            )?;
+
+            Ok(mask)
+        };
+
+        if h_min <= h_max {
+            return in_range(h_min, h_max);
+        }
+
+        let mut mask = Mat::default();
+        core::bitwise_or_def(&in_range(h_min, 179.0)?, &in_range(0.0, h_max)?, &mut mask)?;
+
+        Ok(mask)
+    }
+}
+
+impl Detector for BlobDetector {
+    fn name(&self) -> &'static str {
+        "blob"
+    }
+
+    fn detect(&mut self, frame: &Mat) -> Result<Detections> {
+        let mut hsv = Mat::default();
+        imgproc::cvt_color_def(frame, &mut hsv, imgproc::COLOR_BGR2HSV)?;
+
+        let mut mask = Mat::default();
+        imgproc::morphology_ex_def(
+            &self.mask(&hsv)?,
+            &mut mask,
+            imgproc::MORPH_OPEN,
+            &self.kernel,
+        )?;
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        imgproc::find_contours_def(
+            &mask,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+        )?;
+
+        let mut largest: Option<(f64, Vector<Point>)> = None;
+
+        for contour in contours {
+            let area = imgproc::contour_area_def(&contour)?;
+
+            if area >= self.config.min_area && largest.as_ref().is_none_or(|(max, _)| area > *max) {
+                largest = Some((area, contour));
+            }
+        }
+
+        let Some((area, contour)) = largest else {
+            return Ok(Detections::default());
+        };
+
+        let rect = imgproc::bounding_rect(&contour)?;
+        let moments = imgproc::moments_def(&contour)?;
+
+        let (cx, cy) = if moments.m00 > 0.0 {
+            (
+                (moments.m10 / moments.m00) as i32,
+                (moments.m01 / moments.m00) as i32,
+            )
+        } else {
+            (rect.x + rect.width / 2, rect.y + rect.height / 2)
+        };
+
+        let blob = Blob {
+            label: self.config.label.clone(),
+            cx,
+            cy,
+            area,
+            bbox: BoundingBox {
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                height: rect.height,
+            },
+            frame_width: frame.cols(),
+            frame_height: frame.rows(),
+        };
+
+        Ok(Detections {
+            events: vec![Event::BlobDetected(blob)],
+            annotations: vec![Annotation {
+                rect,
+                label: self.config.label.clone(),
+                colour: (255, 140, 0),
+            }],
+        })
+    }
+}
@@ -0,0 +1,268 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use opencv::{
+    calib3d,
+    core::{self, FileStorage, Mat, Point2f, Point3f, Size, Vector},
+    imgcodecs, imgproc,
+    prelude::*,
+};
+
+// Fewer chessboard views than this rarely constrain the distortion model
+const MIN_VIEWS: usize = 8;
+
+/// Camera intrinsics in OpenCV's calibration file format, YAML or JSON:
+/// `camera_matrix` (3x3), `distortion_coefficients` and optionally
+/// `image_width` / `image_height` of the frames it was calibrated at.
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub camera_matrix: Mat,
+    pub dist_coeffs: Mat,
+    pub image_size: Option<Size>,
+}
+
+impl Calibration {
+    pub fn load(path: &Path) -> Result<Self> {
+        let name = path.to_string_lossy();
+        let storage = FileStorage::new_def(&name, core::FileStorage_READ)
+            .with_context(|| format!("Failed to open calibration {name}"))?;
+
+        if !storage.is_opened()? {
+            bail!("Failed to open calibration {name}");
+        }
+
+        let stored = storage.get_node("camera_matrix")?.mat()?;
+        if stored.rows() != 3 || stored.cols() != 3 {
+            bail!("{name}: camera_matrix must be 3x3");
+        }
+
+        let mut camera_matrix = Mat::default();
+        stored.convert_to_def(&mut camera_matrix, core::CV_64F)?;
+
+        let dist_coeffs = storage.get_node("distortion_coefficients")?.mat()?;
+
+        let width = storage.get_node("image_width")?;
+        let height = storage.get_node("image_height")?;
+        let image_size = if width.is_int()? && height.is_int()? {
+            Some(Size::new(width.to_i32()?, height.to_i32()?))
+        } else {
+            None
+        };
+
+        Ok(Calibration {
+            camera_matrix,
+            dist_coeffs,
+            image_size,
+        })
+    }
+
+    pub fn save(&self, path: &Path, rms_error: f64) -> Result<()> {
+        let name = path.to_string_lossy();
+        let mut storage = FileStorage::new_def(&name, core::FileStorage_WRITE)
+            .with_context(|| format!("Failed to create {name}"))?;
+
+        if let Some(size) = self.image_size {
+            storage.write_i32("image_width", size.width)?;
+            storage.write_i32("image_height", size.height)?;
+        }
+
+        storage.write_mat("camera_matrix", &self.camera_matrix)?;
+        storage.write_mat("distortion_coefficients", &self.dist_coeffs)?;
+        storage.write_f64("rms_error", rms_error)?;
+        storage.release()?;
+
+        Ok(())
+    }
+
+    /// Intrinsics for frames that have already been undistorted
+    pub fn without_distortion(&self) -> Calibration {
+        Calibration {
+            camera_matrix: self.camera_matrix.clone(),
+            dist_coeffs: Mat::default(),
+            image_size: self.image_size,
+        }
+    }
+
+    /// Intrinsics for frames of `size`. Focal lengths and principal point
+    /// scale with the resolution; distortion is unchanged.
+    pub fn scaled_to(&self, size: Size) -> Result<Calibration> {
+        let Some(from) = self.image_size.filter(|from| *from != size) else {
+            return Ok(self.clone());
+        };
+
+        let sx = size.width as f64 / from.width as f64;
+        let sy = size.height as f64 / from.height as f64;
+
+        let mut camera_matrix = self.camera_matrix.clone();
+        for col in 0..3 {
+            *camera_matrix.at_2d_mut::<f64>(0, col)? *= sx;
+            *camera_matrix.at_2d_mut::<f64>(1, col)? *= sy;
+        }
+
+        Ok(Calibration {
+            camera_matrix,
+            dist_coeffs: self.dist_coeffs.clone(),
+            image_size: Some(size),
+        })
+    }
+}
+
+/// Printed chessboard target, measured in inner corners rather than squares
+#[derive(Debug, Clone, Copy)]
+pub struct Chessboard {
+    pub columns: i32,
+    pub rows: i32,
+    /// Edge length of one square. Only matters for the extrinsics, the
+    /// intrinsics come out the same whatever the unit.
+    pub square_size: f32,
+}
+
+impl Chessboard {
+    fn object_points(&self) -> Vector<Point3f> {
+        (0..self.rows)
+            .flat_map(|row| {
+                (0..self.columns).map(move |col| {
+                    Point3f::new(
+                        col as f32 * self.square_size,
+                        row as f32 * self.square_size,
+                        0.0,
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Result of calibrating from a set of chessboard images
+pub struct CalibrationRun {
+    pub calibration: Calibration,
+    /// Reprojection error in pixels, below 1.0 is a usable calibration
+    pub rms_error: f64,
+    pub used: Vec<PathBuf>,
+    pub rejected: Vec<PathBuf>,
+}
+
+/// Finds the chessboard in each image and solves for the camera intrinsics.
+/// Images where the whole board is not visible, or whose size differs from
+/// the first image, are rejected.
+pub fn calibrate(images: &[PathBuf], board: Chessboard) -> Result<CalibrationRun> {
+    let pattern = Size::new(board.columns, board.rows);
+    let object = board.object_points();
+
+    let mut image_size: Option<Size> = None;
+    let mut object_points = Vector::<Vector<Point3f>>::new();
+    let mut image_points = Vector::<Vector<Point2f>>::new();
+    let mut used = Vec::new();
+    let mut rejected = Vec::new();
+
+    for path in images {
+        let image = imgcodecs::imread(&path.to_string_lossy(), imgcodecs::IMREAD_COLOR)?;
+        let size = image.size()?;
+
+        if size.width == 0 || image_size.is_some_and(|first| first != size) {
+            rejected.push(path.clone());
+            continue;
+        }
+
+        let mut gray = Mat::default();
+        imgproc::cvt_color_def(&image, &mut gray, imgproc::COLOR_BGR2GRAY)?;
+
+        let mut corners = Vector::<Point2f>::new();
+        if !calib3d::find_chessboard_corners_sb_def(&gray, pattern, &mut corners)? {
+            rejected.push(path.clone());
+            continue;
+        }
+
+        image_size = Some(size);
+        object_points.push(object.clone());
+        image_points.push(corners);
+        used.push(path.clone());
+    }
+
+    let Some(image_size) = image_size.filter(|_| used.len() >= MIN_VIEWS) else {
+        bail!(
+            "Found the chessboard in {} of {} images, need at least {MIN_VIEWS}",
+            used.len(),
+            images.len()
+        );
+    };
+
+    let mut camera_matrix = Mat::default();
+    let mut dist_coeffs = Mat::default();
+    let mut rvecs = Vector::<Mat>::new();
+    let mut tvecs = Vector::<Mat>::new();
+
+    let rms_error = calib3d::calibrate_camera_def(
+        &object_points,
+        &image_points,
+        image_size,
+        &mut camera_matrix,
+        &mut dist_coeffs,
+        &mut rvecs,
+        &mut tvecs,
+    )?;
+
+    Ok(CalibrationRun {
+        calibration: Calibration {
+            camera_matrix,
+            dist_coeffs,
+            image_size: Some(image_size),
+        },
+        rms_error,
+        used,
+        rejected,
+    })
+}
+
+/// Removes lens distortion from frames, keeping the calibrated focal length
+/// so the undistorted image can be used with zero distortion coefficients
+pub struct Undistorter {
+    calibration: Calibration,
+    // Remap tables for the frame size they were built for
+    maps: Option<(Size, Mat, Mat)>,
+}
+
+impl Undistorter {
+    pub fn new(calibration: Calibration) -> Self {
+        Undistorter {
+            calibration,
+            maps: None,
+        }
+    }
+
+    pub fn apply(&mut self, frame: Mat) -> Result<Mat> {
+        let size = frame.size()?;
+
+        if self
+            .maps
+            .as_ref()
+            .is_none_or(|(built, _, _)| *built != size)
+        {
+            let calibration = self.calibration.scaled_to(size)?;
+            let mut map1 = Mat::default();
+            let mut map2 = Mat::default();
+
+            calib3d::init_undistort_rectify_map(
+                &calibration.camera_matrix,
+                &calibration.dist_coeffs,
+                &Mat::default(),
+                &calibration.camera_matrix,
+                size,
+                core::CV_16SC2,
+                &mut map1,
+                &mut map2,
+            )?;
+
+            self.maps = Some((size, map1, map2));
+        }
+
+        let Some((_, map1, map2)) = &self.maps else {
+            return Ok(frame);
+        };
+
+        let mut undistorted = Mat::default();
+        imgproc::remap_def(&frame, &mut undistorted, map1, map2, imgproc::INTER_LINEAR)?;
+
+        Ok(undistorted)
+    }
+}
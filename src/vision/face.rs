@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use opencv::{
+    core::{Mat, Rect, Size, Vector},
+    imgproc,
+    objdetect::CascadeClassifier,
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::bus::event::{BoundingBox, Event, Face};
+use crate::vision::{Annotation, Detections, Detector};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FaceConfig {
+    pub enabled: bool,
+    /// Haar cascade XML, as shipped in OpenCV's `haarcascades` data directory
+    pub cascade: PathBuf,
+    /// Faces smaller than this many pixels across are ignored
+    pub min_size: i32,
+    /// How many overlapping detections a face needs, higher means fewer
+    /// false positives
+    pub min_neighbors: i32,
+}
+
+impl Default for FaceConfig {
+    fn default() -> Self {
+        FaceConfig {
+            enabled: false,
+            cascade: PathBuf::from(
+                "/usr/share/opencv4/haarcascades/haarcascade_frontalface_default.xml",
+            ),
+            min_size: 40,
+            min_neighbors: 5,
+        }
+    }
+}
+
+/// Haar-cascade face detector, reporting the largest face in view
+pub struct FaceDetector {
+    config: FaceConfig,
+    classifier: CascadeClassifier,
+}
+
+impl FaceDetector {
+    pub fn new(config: FaceConfig) -> Result<Self> {
+        let path = config.cascade.to_string_lossy();
+        let classifier = CascadeClassifier::new(&path)
+            .with_context(|| format!("Failed to load face cascade {path}"))?;
+
+        if classifier.empty()? {
+            bail!("Face cascade {path} is empty or missing");
+        }
+
+        Ok(FaceDetector { config, classifier })
+    }
+}
+
+impl Detector for FaceDetector {
+    fn name(&self) -> &'static str {
+        "face"
+    }
+
+    fn detect(&mut self, frame: &Mat) -> Result<Detections> {
+        let mut gray = Mat::default();
+        imgproc::cvt_color_def(frame, &mut gray, imgproc::COLOR_BGR2GRAY)?;
+
+        let mut equalized = Mat::default();
+        imgproc::equalize_hist(&gray, &mut equalized)?;
+
+        let mut faces = Vector::<Rect>::new();
+        self.classifier.detect_multi_scale(
+            &equalized,
+            &mut faces,
+            1.1,
+            self.config.min_neighbors,
+            0,
+            Size::new(self.config.min_size, self.config.min_size),
+            Size::default(),
+        )?;
+
+        let Some(largest) = faces.iter().max_by_key(|face| face.area()) else {
+            return Ok(Detections::default());
+        };
+
+        let face = Face {
+            bbox: BoundingBox {
+                x: largest.x,
+                y: largest.y,
+                width: largest.width,
+                height: largest.height,
+            },
+            cx: largest.x + largest.width / 2,
+            cy: largest.y + largest.height / 2,
+            faces: faces.len(),
+            frame_width: frame.cols(),
+            frame_height: frame.rows(),
+        };
+
+        Ok(Detections {
+            events: vec![Event::FaceDetected(face)],
+            annotations: faces
+                .iter()
+                .map(|rect| Annotation {
+                    rect,
+                    label: "face".to_string(),
+                    colour: (0, 255, 0),
+                })
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,91 @@
+pub mod aruco;
+pub mod blob;
+pub mod calibration;
+pub mod face;
+pub mod motion;
+pub mod overlay;
+pub mod qr;
+
+use anyhow::Result;
+use opencv::core::{Mat, Rect};
+use serde::Deserialize;
+
+use crate::vision::{
+    aruco::ArucoConfig, blob::BlobConfig, face::FaceConfig, motion::MotionConfig, qr::QrConfig,
+};
+use crate::{
+    bus::event::{Event, Mode},
+    config::Config,
+};
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct VisionConfig {
+    pub blob: BlobConfig,
+    pub aruco: ArucoConfig,
+    pub motion: MotionConfig,
+    pub qr: QrConfig,
+    pub face: FaceConfig,
+}
+
+/// A labelled box drawn over the MJPEG stream
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub rect: Rect,
+    pub label: String,
+    pub colour: (u8, u8, u8),
+}
+
+/// What a detector found in one frame
+#[derive(Debug, Default)]
+pub struct Detections {
+    pub events: Vec<Event>,
+    pub annotations: Vec<Annotation>,
+}
+
+/// A vision algorithm run by the vision node on every camera frame
+pub trait Detector: Send {
+    fn name(&self) -> &'static str;
+
+    /// Whether to run while the robot is in `mode`
+    fn runs_in(&self, _mode: Mode) -> bool {
+        true
+    }
+
+    fn detect(&mut self, frame: &Mat) -> Result<Detections>;
+}
+
+/// Detectors enabled in the config
+pub fn detectors(config: &Config) -> Result<Vec<Box<dyn Detector>>> {
+    let camera = &config.camera;
+    let config = &config.vision;
+
+    let mut detectors: Vec<Box<dyn Detector>> = Vec::new();
+
+    if config.blob.enabled {
+        detectors.push(Box::new(blob::BlobDetector::new(config.blob.clone())?));
+    }
+
+    if config.aruco.enabled {
+        detectors.push(Box::new(aruco::MarkerDetector::new(
+            config.aruco.clone(),
+            camera,
+        )?));
+    }
+
+    if config.motion.enabled {
+        detectors.push(Box::new(motion::MotionDetector::new(
+            config.motion.clone(),
+        )?));
+    }
+
+    if config.qr.enabled {
+        detectors.push(Box::new(qr::QrDetector::new(config.qr.clone())?));
+    }
+
+    if config.face.enabled {
+        detectors.push(Box::new(face::FaceDetector::new(config.face.clone())?));
+    }
+
+    Ok(detectors)
+}
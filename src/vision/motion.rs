@@ -0,0 +1,193 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use opencv::{
+    core::{self, Mat, Point, Ptr, Size, Vector},
+    imgproc,
+    prelude::*,
+    video::{self, BackgroundSubtractorMOG2, BackgroundSubtractorMOG2Trait},
+};
+use serde::Deserialize;
+
+use crate::behaviour::sentry;
+use crate::bus::event::{BoundingBox, Event, Mode, Motion};
+use crate::vision::{Annotation, Detections, Detector};
+
+// A gap this long between frames means the detector was paused or the camera
+// reopened, so the background model is out of date
+const RESET_GAP: Duration = Duration::from_secs(1);
+
+// MOG2 marks shadows as 127 and foreground as 255
+const FOREGROUND_LEVEL: f64 = 200.0;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MotionConfig {
+    pub enabled: bool,
+    /// Fraction of the frame that has to change to count as motion
+    pub threshold: f64,
+    /// Moving regions smaller than this many pixels are ignored as noise
+    pub min_area: f64,
+    /// Frames the background model remembers
+    pub history: i32,
+    /// How far a pixel may drift from the background before it is foreground
+    pub var_threshold: f64,
+    /// Seconds to wait after reporting motion before watching again, long
+    /// enough for the servo to settle
+    pub cooldown_secs: f64,
+}
+
+impl Default for MotionConfig {
+    fn default() -> Self {
+        MotionConfig {
+            enabled: false,
+            threshold: 0.02,
+            min_area: 500.0,
+            history: 500,
+            var_threshold: 16.0,
+            cooldown_secs: 3.0,
+        }
+    }
+}
+
+/// Background subtraction (MOG2) on a still camera. Only runs in sentry mode,
+/// when the robot is not moving the camera itself.
+pub struct MotionDetector {
+    config: MotionConfig,
+    subtractor: Ptr<BackgroundSubtractorMOG2>,
+    kernel: Mat,
+    last_frame: Option<Instant>,
+    quiet_until: Option<Instant>,
+    relearn: bool,
+}
+
+impl MotionDetector {
+    pub fn new(config: MotionConfig) -> Result<Self> {
+        let subtractor =
+            video::create_background_subtractor_mog2(config.history, config.var_threshold, true)?;
+        let kernel = imgproc::get_structuring_element_def(imgproc::MORPH_ELLIPSE, Size::new(5, 5))?;
+
+        Ok(MotionDetector {
+            config,
+            subtractor,
+            kernel,
+            last_frame: None,
+            quiet_until: None,
+            relearn: true,
+        })
+    }
+}
+
+impl Detector for MotionDetector {
+    fn name(&self) -> &'static str {
+        "motion"
+    }
+
+    fn runs_in(&self, mode: Mode) -> bool {
+        mode == Mode::Automatic(sentry::NAME)
+    }
+
+    fn detect(&mut self, frame: &Mat) -> Result<Detections> {
+        let now = Instant::now();
+
+        if self
+            .last_frame
+            .is_none_or(|last| now.duration_since(last) > RESET_GAP)
+        {
+            self.relearn = true;
+        }
+        self.last_frame = Some(now);
+
+        if self.quiet_until.is_some_and(|until| now < until) {
+            return Ok(Detections::default());
+        }
+
+        let mut foreground = Mat::default();
+
+        // Take this frame as the whole background rather than blending it in
+        if self.relearn {
+            self.relearn = false;
+            BackgroundSubtractorMOG2Trait::apply(
+                &mut self.subtractor,
+                frame,
+                &mut foreground,
+                1.0,
+            )?;
+            return Ok(Detections::default());
+        }
+
+        // -1 lets MOG2 pick the learning rate from its history
+        BackgroundSubtractorMOG2Trait::apply(&mut self.subtractor, frame, &mut foreground, -1.0)?;
+
+        let mut thresholded = Mat::default();
+        imgproc::threshold(
+            &foreground,
+            &mut thresholded,
+            FOREGROUND_LEVEL,
+            255.0,
+            imgproc::THRESH_BINARY,
+        )?;
+
+        let mut mask = Mat::default();
+        imgproc::morphology_ex_def(&thresholded, &mut mask, imgproc::MORPH_OPEN, &self.kernel)?;
+
+        let pixels = (frame.cols() * frame.rows()).max(1) as f64;
+        let fraction = core::count_non_zero(&mask)? as f64 / pixels;
+
+        if fraction < self.config.threshold {
+            return Ok(Detections::default());
+        }
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        imgproc::find_contours_def(
+            &mask,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+        )?;
+
+        let mut largest: Option<(f64, Vector<Point>)> = None;
+
+        for contour in contours {
+            let area = imgproc::contour_area_def(&contour)?;
+
+            if area >= self.config.min_area && largest.as_ref().is_none_or(|(max, _)| area > *max) {
+                largest = Some((area, contour));
+            }
+        }
+
+        let Some((_, contour)) = largest else {
+            return Ok(Detections::default());
+        };
+
+        let rect = imgproc::bounding_rect(&contour)?;
+
+        // The robot is likely to react by moving the camera, so look away
+        // for a while and start over with a fresh background
+        self.quiet_until = Some(now + Duration::from_secs_f64(self.config.cooldown_secs));
+        self.relearn = true;
+
+        let motion = Motion {
+            fraction,
+            bbox: BoundingBox {
+                x: rect.x,
+                y: rect.y,
+                width: rect.width,
+                height: rect.height,
+            },
+            cx: rect.x + rect.width / 2,
+            cy: rect.y + rect.height / 2,
+            frame_width: frame.cols(),
+            frame_height: frame.rows(),
+        };
+
+        Ok(Detections {
+            events: vec![Event::MotionDetected(motion)],
+            annotations: vec![Annotation {
+                rect,
+                label: format!("motion {:.0}%", fraction * 100.0),
+                colour: (255, 0, 0),
+            }],
+        })
+    }
+}
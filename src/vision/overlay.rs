@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use chrono::Utc;
+use opencv::{
+    core::{Mat, Point, Rect, Scalar},
+    imgproc,
+    prelude::*,
+};
+
+use crate::bus::{event::MotorDirection, state::RobotState};
+use crate::vision::Annotation;
+
+// Annotations not refreshed within this time are no longer drawn
+const ANNOTATION_TTL: Duration = Duration::from_secs(1);
+
+// Distance shown as a full bar, cm
+const DISTANCE_FULL_SCALE: f64 = 100.0;
+
+// Annotations from one source and when they were set
+type Entry = (Instant, Vec<Annotation>);
+
+/// Latest annotations from each detector, drawn onto frames by the camera
+/// node together with a HUD of the robot's state
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    annotations: Arc<Mutex<HashMap<&'static str, Entry>>>,
+    viewers: Arc<AtomicUsize>,
+}
+
+/// Held by each stream showing the overlay. The camera node only renders
+/// the overlay while at least one is alive.
+pub struct OverlayViewer {
+    viewers: Arc<AtomicUsize>,
+}
+
+impl Drop for OverlayViewer {
+    fn drop(&mut self) {
+        self.viewers.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Overlay {
+    /// Replaces everything previously set by `source`
+    pub fn set(&self, source: &'static str, annotations: Vec<Annotation>) {
+        self.annotations
+            .lock()
+            .unwrap()
+            .insert(source, (Instant::now(), annotations));
+    }
+
+    pub fn viewer(&self) -> OverlayViewer {
+        self.viewers.fetch_add(1, Ordering::Relaxed);
+
+        OverlayViewer {
+            viewers: self.viewers.clone(),
+        }
+    }
+
+    pub fn has_viewers(&self) -> bool {
+        self.viewers.load(Ordering::Relaxed) > 0
+    }
+
+    /// Copy of `frame` with the HUD and current annotations drawn on
+    pub fn render(&self, frame: &Mat, state: &RobotState) -> Result<Mat> {
+        let annotations: Vec<Annotation> = {
+            let mut guard = self.annotations.lock().unwrap();
+            guard.retain(|_, (set_at, _)| set_at.elapsed() < ANNOTATION_TTL);
+            guard
+                .values()
+                .flat_map(|(_, annotations)| annotations.iter().cloned())
+                .collect()
+        };
+
+        let mut rendered = frame.clone();
+
+        for annotation in &annotations {
+            let (r, g, b) = annotation.colour;
+            let colour = Scalar::new(b as f64, g as f64, r as f64, 0.0);
+            let rect = annotation.rect;
+
+            imgproc::rectangle(&mut rendered, rect, colour, 2, imgproc::LINE_8, 0)?;
+            text(
+                &mut rendered,
+                &annotation.label,
+                Point::new(rect.x, (rect.y - 6).max(12)),
+                colour,
+            )?;
+        }
+
+        hud(&mut rendered, state)?;
+
+        Ok(rendered)
+    }
+}
+
+// Time, mode and motor top left, LDRs top right, distance bar bottom left
+fn hud(frame: &mut Mat, state: &RobotState) -> Result<()> {
+    let white = Scalar::all(255.0);
+    let (width, height) = (frame.cols(), frame.rows());
+
+    let time = Utc::now().format("%Y-%m-%d %H:%M:%S UTC").to_string();
+    text(frame, &time, Point::new(8, 20), white)?;
+
+    let mode = state.mode.to_string().to_uppercase();
+    text(frame, &mode, Point::new(8, 40), white)?;
+
+    let motor = match (&state.motor, &state.wheels) {
+        (Some(cmd), _) if cmd.direction != MotorDirection::Stop => {
+            format!("{:?} {}", cmd.direction, cmd.speed).to_uppercase()
+        }
+        (_, Some(wheels)) if (wheels.left, wheels.right) != (0, 0) => {
+            format!("L {} R {}", wheels.left, wheels.right)
+        }
+        _ => "STOPPED".to_string(),
+    };
+    text(frame, &motor, Point::new(8, 60), white)?;
+
+    if let Some(ldr) = &state.ldr {
+        let readings = [("L", ldr.l_val), ("M", ldr.m_val), ("R", ldr.r_val)];
+
+        for (i, (label, value)) in readings.into_iter().enumerate() {
+            let centre = Point::new(width - 76 + i as i32 * 26, 16);
+            let fill = if value == 1 { imgproc::FILLED } else { 2 };
+
+            imgproc::circle(frame, centre, 8, white, fill, imgproc::LINE_AA, 0)?;
+            text(frame, label, Point::new(centre.x - 5, 42), white)?;
+        }
+    }
+
+    if let Some(ultrasound) = &state.ultrasound {
+        let fraction = (ultrasound.distance / DISTANCE_FULL_SCALE).clamp(0.0, 1.0);
+        let bar = Rect::new(8, height - 24, width / 3, 12);
+        let filled = Rect::new(
+            bar.x,
+            bar.y,
+            (bar.width as f64 * fraction) as i32,
+            bar.height,
+        );
+        // Red when close, green when clear
+        let colour = Scalar::new(0.0, 255.0 * fraction, 255.0 * (1.0 - fraction), 0.0);
+
+        imgproc::rectangle(frame, filled, colour, imgproc::FILLED, imgproc::LINE_8, 0)?;
+        imgproc::rectangle(frame, bar, white, 1, imgproc::LINE_8, 0)?;
+        text(
+            frame,
+            &format!("{:.1} cm", ultrasound.distance),
+            Point::new(bar.x + bar.width + 8, bar.y + bar.height),
+            white,
+        )?;
+    }
+
+    Ok(())
+}
+
+// Text with a dark outline so it reads over any background
+fn text(frame: &mut Mat, text: &str, origin: Point, colour: Scalar) -> Result<()> {
+    for (colour, thickness) in [(Scalar::all(0.0), 3), (colour, 1)] {
+        imgproc::put_text(
+            frame,
+            text,
+            origin,
+            imgproc::FONT_HERSHEY_SIMPLEX,
+            0.5,
+            colour,
+            thickness,
+            imgproc::LINE_AA,
+            false,
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result, bail};
+use opencv::{
+    core::{Mat, Point, Point2f, Vector},
+    imgproc,
+    objdetect::QRCodeDetector,
+    prelude::*,
+};
+use serde::Deserialize;
+
+use crate::behaviour;
+use crate::bus::event::{
+    BoundingBox, Event, Mode, ModeCommand, MotorCommand, MotorDirection, QrCode, RecordCommand,
+    ServoCommand,
+};
+use crate::vision::{Annotation, Detections, Detector};
+
+// Speed used by `drive:` actions that don't give one
+const DEFAULT_DRIVE_SPEED: u8 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct QrConfig {
+    pub enabled: bool,
+    /// Payload text to the action it triggers: `stop`, `mode:<manual|behaviour>`,
+    /// `servo:<angle>`, `drive:<direction>[:<speed>]` or `record:<start|stop>`
+    pub actions: BTreeMap<String, String>,
+    /// A code has to be out of view this long before showing it again
+    /// repeats its action
+    pub repeat_secs: f64,
+}
+
+impl Default for QrConfig {
+    fn default() -> Self {
+        QrConfig {
+            enabled: false,
+            actions: BTreeMap::new(),
+            repeat_secs: 3.0,
+        }
+    }
+}
+
+/// Parses a configured action into the command event it publishes
+fn parse_action(action: &str) -> Result<Event> {
+    let mut parts = action.split(':');
+    let name = parts.next().unwrap_or_default();
+    let arg = parts.next();
+    let extra = parts.next();
+
+    let event = match (name, arg, extra) {
+        ("stop", None, None) => Event::MotorCommand(MotorCommand {
+            direction: MotorDirection::Stop,
+            speed: 0,
+        }),
+        ("mode", Some(mode), None) => {
+            let mode = match mode {
+                "manual" => Mode::Manual,
+                "automatic" => Mode::Automatic(behaviour::DEFAULT),
+                name => match behaviour::lookup(name) {
+                    Some(name) => Mode::Automatic(name),
+                    None => bail!("Unknown mode {mode:?}"),
+                },
+            };
+
+            Event::ModeCommand(ModeCommand { mode })
+        }
+        ("servo", Some(angle), None) => {
+            let angle: u8 = angle.parse().context("Invalid servo angle")?;
+            if angle > 180 {
+                bail!("Servo angle must be 0..=180, got {angle}");
+            }
+
+            Event::ServoCommand(ServoCommand { angle })
+        }
+        ("drive", Some(direction), speed) => {
+            let direction = match direction {
+                "forward" => MotorDirection::Forward,
+                "backward" => MotorDirection::Backward,
+                "left" => MotorDirection::Left,
+                "right" => MotorDirection::Right,
+                _ => bail!("Unknown direction {direction:?}"),
+            };
+            let speed = match speed {
+                Some(speed) => speed.parse().context("Invalid drive speed")?,
+                None => DEFAULT_DRIVE_SPEED,
+            };
+            if speed > 100 {
+                bail!("Drive speed must be 0..=100, got {speed}");
+            }
+
+            Event::MotorCommand(MotorCommand { direction, speed })
+        }
+        ("record", Some("start"), None) => Event::RecordCommand(RecordCommand { recording: true }),
+        ("record", Some("stop"), None) => Event::RecordCommand(RecordCommand { recording: false }),
+        _ => bail!("Unknown action {action:?}"),
+    };
+
+    Ok(event)
+}
+
+/// Decodes QR codes in view and runs the action mapped to each payload
+pub struct QrDetector {
+    detector: QRCodeDetector,
+    actions: HashMap<String, Event>,
+    repeat_after: Duration,
+    // Last time each payload was in view
+    seen: HashMap<String, Instant>,
+}
+
+impl QrDetector {
+    pub fn new(config: QrConfig) -> Result<Self> {
+        let actions = config
+            .actions
+            .iter()
+            .map(|(payload, action)| {
+                let event = parse_action(action)
+                    .with_context(|| format!("Bad QR action for {payload:?}"))?;
+
+                Ok((payload.clone(), event))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        Ok(QrDetector {
+            detector: QRCodeDetector::default()?,
+            actions,
+            repeat_after: Duration::from_secs_f64(config.repeat_secs),
+            seen: HashMap::new(),
+        })
+    }
+}
+
+impl Detector for QrDetector {
+    fn name(&self) -> &'static str {
+        "qr"
+    }
+
+    fn detect(&mut self, frame: &Mat) -> Result<Detections> {
+        let now = Instant::now();
+        self.seen
+            .retain(|_, last| now.duration_since(*last) < self.repeat_after);
+
+        let mut decoded = Vector::<String>::new();
+        let mut points = Mat::default();
+        let mut straight = Mat::default();
+
+        if !self.detector.detect_and_decode_multi(
+            frame,
+            &mut decoded,
+            &mut points,
+            &mut straight,
+        )? {
+            return Ok(Detections::default());
+        }
+
+        let mut detections = Detections::default();
+        // Four corners per code, in the same order as the decoded text
+        let corners = points.data_typed::<Point2f>()?;
+
+        for (text, quad) in decoded.iter().zip(corners.chunks(4)) {
+            if text.is_empty() {
+                continue;
+            }
+
+            let quad: Vector<Point> = quad
+                .iter()
+                .map(|p| Point::new(p.x as i32, p.y as i32))
+                .collect();
+            let rect = imgproc::bounding_rect(&quad)?;
+
+            // Holding a card up triggers it once, not on every frame
+            let fresh = self.seen.insert(text.clone(), now).is_none();
+            let action = self.actions.get(&text).filter(|_| fresh);
+
+            if let Some(action) = action {
+                println!("QR code {text:?} triggered {action:?}");
+                detections.events.push(action.clone());
+            }
+
+            detections.annotations.push(Annotation {
+                rect,
+                label: text.clone(),
+                colour: (255, 0, 255),
+            });
+            detections.events.push(Event::QrCodeDetected(QrCode {
+                text,
+                bbox: BoundingBox {
+                    x: rect.x,
+                    y: rect.y,
+                    width: rect.width,
+                    height: rect.height,
+                },
+                frame_width: frame.cols(),
+                frame_height: frame.rows(),
+            }));
+        }
+
+        Ok(detections)
+    }
+}